@@ -0,0 +1,689 @@
+//! `fgp-workflow` command-line tool.
+//!
+//! ```text
+//! fgp-workflow run digest.yaml --input account=work
+//! fgp-workflow validate digest.yaml --manifests manifests/
+//! fgp-workflow plan digest.yaml --input account=work --output json
+//! fgp-workflow plan digest.yaml --watch
+//! fgp-workflow graph digest.yaml --format mermaid
+//! fgp-workflow schedule digest.yaml triage.yaml
+//! fgp-workflow run smoke.yaml --junit smoke.xml --report smoke.json
+//! fgp-workflow report smoke.json --format markdown
+//! fgp-workflow history digest --failed -n 1
+//! fgp-workflow list workflows/
+//! fgp-workflow serve workflows/
+//! ```
+
+mod output;
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use fgp_workflow::graph::Graph;
+use fgp_workflow::history::{History, RunFilter, RunStatus};
+use fgp_workflow::manifest::Manifests;
+use fgp_workflow::registry::WorkflowRegistry;
+use fgp_workflow::report::Report;
+use fgp_workflow::schedule::Scheduler;
+use fgp_workflow::service::WorkflowService;
+use fgp_workflow::watch::{Outcome, Watcher};
+use fgp_workflow::{Context, ExecutionResult, Value, Workflow};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Run and inspect FGP workflows.
+#[derive(Debug, Parser)]
+#[command(name = "fgp-workflow", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run a workflow
+    Run {
+        #[command(flatten)]
+        args: RunArgs,
+
+        #[command(flatten)]
+        options: RunOptions,
+    },
+
+    /// Check a workflow for problems without running it
+    Validate {
+        /// Workflow file (YAML, JSON or TOML)
+        file: PathBuf,
+
+        /// Directory of service manifests to check methods and params against
+        #[arg(long, value_name = "DIR")]
+        manifests: Option<PathBuf>,
+    },
+
+    /// Show what a workflow would run, with rendered params, without
+    /// calling any service
+    Plan(RunArgs),
+
+    /// Show a workflow's steps and the data flowing between them
+    Graph {
+        /// Workflow file (YAML, JSON or TOML)
+        file: PathBuf,
+
+        /// How to draw the graph
+        #[arg(short, long, value_enum, default_value_t = GraphFormat::Text)]
+        format: GraphFormat,
+    },
+
+    /// Run workflows on their schedules until interrupted
+    ///
+    /// On Ctrl-C or SIGTERM no new runs start, and the runs in progress are
+    /// waited for.
+    Schedule {
+        /// Workflow files with a `schedule:` field
+        files: Vec<PathBuf>,
+
+        /// Schedule manifest listing workflows and their cron expressions
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+    },
+
+    /// Render a JSON report saved by `run --report`
+    Report {
+        /// Report file
+        file: PathBuf,
+
+        /// How to render it
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+    },
+
+    /// Show recorded runs, most recent first
+    History {
+        /// Only runs of this workflow
+        workflow: Option<String>,
+
+        /// Only failed runs
+        #[arg(long)]
+        failed: bool,
+
+        /// Number of runs to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+
+        /// Print one run in full, as JSON
+        #[arg(long, value_name = "ID", conflicts_with_all = ["workflow", "failed"])]
+        show: Option<String>,
+    },
+
+    /// List the workflows in directories and `~/.fgp/workflows`
+    List {
+        /// Directories of workflow files
+        dirs: Vec<PathBuf>,
+    },
+
+    /// Serve directories of workflows as the `workflow` daemon
+    Serve {
+        /// Directories of workflow files, besides `~/.fgp/workflows`
+        dirs: Vec<PathBuf>,
+
+        /// Socket to listen on [default: ~/.fgp/services/workflow/daemon.sock]
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+    /// Workflow file (YAML, JSON or TOML)
+    file: PathBuf,
+
+    /// Input variable; values are parsed as JSON when they can be
+    #[arg(short, long = "input", value_name = "KEY=VALUE", value_parser = parse_input)]
+    inputs: Vec<(String, Value)>,
+
+    /// How to print the result
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    /// Run again whenever the workflow file or its includes change
+    #[arg(short, long)]
+    watch: bool,
+}
+
+/// Options of `run` that plans don't have.
+///
+/// Reports describe a single run, so they can't be asked for with `--watch`.
+/// Each run `--watch` starts is recorded in the history like any other.
+#[derive(Debug, Args)]
+struct RunOptions {
+    /// Don't record the run in the run history (`~/.fgp/history`)
+    #[arg(long)]
+    no_history: bool,
+
+    /// Write a JSON report of the run to FILE
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    report: Option<PathBuf>,
+
+    /// Write a JUnit XML report of the run, a test case per step, to FILE
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    junit: Option<PathBuf>,
+
+    /// Write a Markdown summary of the run to FILE
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    markdown: Option<PathBuf>,
+}
+
+/// How saved reports are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ReportFormat {
+    /// JSON report
+    Json,
+    /// JUnit XML
+    Junit,
+    /// Markdown summary
+    Markdown,
+}
+
+/// How results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable table
+    Table,
+    /// JSON report (see `fgp_workflow::report`)
+    Json,
+}
+
+/// How step graphs are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GraphFormat {
+    /// Indented listing
+    Text,
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+/// Build a context with input variables.
+fn context(inputs: &[(String, Value)]) -> Context {
+    let mut ctx = Context::new();
+    for (key, value) in inputs {
+        ctx.set(key, value.clone());
+    }
+    ctx
+}
+
+/// Parse a `KEY=VALUE` input.
+fn parse_input(s: &str) -> Result<(String, Value), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", s))?;
+    if key.is_empty() {
+        return Err(format!("missing input name in '{}'", s));
+    }
+
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    Ok((key.to_string(), value))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Run { args, options } => run(&args, Some(&options)),
+        Command::Plan(args) => run(&args, None),
+        Command::Validate { file, manifests } => validate(&file, manifests),
+        Command::Graph { file, format } => graph(&file, format),
+        Command::Schedule { files, manifest } => schedule(&files, manifest.as_deref()),
+        Command::Report { file, format } => report(&file, format),
+        Command::History {
+            workflow,
+            failed,
+            limit,
+            show,
+        } => history(workflow.as_deref(), failed, limit, show.as_deref()),
+        Command::List { dirs } => list(&dirs),
+        Command::Serve { dirs, socket } => serve(&dirs, socket),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run a workflow, or plan it when there are no run options, and print
+/// the result.
+fn run(args: &RunArgs, options: Option<&RunOptions>) -> Result<ExitCode> {
+    let dry_run = options.is_none();
+    if args.watch {
+        watch(args, options);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let workflow = fgp_workflow::load_file(&args.file)?;
+    let ctx = context(&args.inputs);
+
+    let outcome = match options {
+        None => fgp_workflow::dry_run(&workflow, ctx),
+        Some(options) => {
+            let started_at = Local::now();
+            let outcome = fgp_workflow::execute_with_context(&workflow, ctx);
+            if !options.no_history {
+                record(&workflow, &args.inputs, started_at, outcome.as_ref());
+            }
+            outcome
+        }
+    };
+
+    let code = print_outcome(
+        &workflow,
+        &args.inputs,
+        outcome.as_ref(),
+        args.output,
+        dry_run,
+    );
+
+    // The outcome is shown even if a report can't be written
+    if let Some(options) = options {
+        if let Err(error) = write_reports(&workflow, &args.inputs, outcome.as_ref(), options) {
+            eprintln!("error: {:#}", error);
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(code)
+}
+
+/// Write the reports a run was asked for.
+fn write_reports(
+    workflow: &Workflow,
+    inputs: &[(String, Value)],
+    outcome: Result<&ExecutionResult, &anyhow::Error>,
+    options: &RunOptions,
+) -> Result<()> {
+    if options.report.is_none() && options.junit.is_none() && options.markdown.is_none() {
+        return Ok(());
+    }
+
+    let report = Report::new(workflow, outcome).inputs(inputs.iter().cloned());
+    if let Some(ref path) = options.report {
+        report.save(path)?;
+    }
+    let rendered = [
+        (&options.junit, ReportFormat::Junit),
+        (&options.markdown, ReportFormat::Markdown),
+    ];
+    for (path, format) in rendered {
+        if let Some(path) = path {
+            std::fs::write(path, render(&report, format))
+                .with_context(|| format!("Failed to write report: {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn render(report: &Report, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Json => report.to_json() + "\n",
+        ReportFormat::Junit => report.to_junit(),
+        ReportFormat::Markdown => report.to_markdown(),
+    }
+}
+
+/// Print a saved report.
+fn report(file: &Path, format: ReportFormat) -> Result<ExitCode> {
+    print!("{}", render(&Report::load(file)?, format));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The default run history, unless there's no home directory.
+fn default_history() -> Option<History> {
+    History::default_path().map(History::open)
+}
+
+/// Record a run in the default history, warning if it can't be written.
+fn record(
+    workflow: &Workflow,
+    inputs: &[(String, Value)],
+    started_at: DateTime<Local>,
+    outcome: Result<&ExecutionResult, &anyhow::Error>,
+) {
+    let Some(history) = default_history() else {
+        return;
+    };
+    let inputs = inputs.iter().cloned().collect();
+    if let Err(error) = history.record(workflow, &inputs, started_at, outcome) {
+        eprintln!("warning: {:#}", error);
+    }
+}
+
+/// Print a run's result, or its failure.
+fn print_outcome(
+    workflow: &Workflow,
+    inputs: &[(String, Value)],
+    outcome: Result<&ExecutionResult, &anyhow::Error>,
+    format: OutputFormat,
+    dry_run: bool,
+) -> ExitCode {
+    match (format, outcome) {
+        (OutputFormat::Json, _) => {
+            let report = Report::new(workflow, outcome)
+                .inputs(inputs.iter().cloned())
+                .dry_run(dry_run);
+            println!("{}", report.to_json());
+        }
+        (OutputFormat::Table, Ok(result)) => {
+            print!("{}", output::table(workflow, result, dry_run))
+        }
+        (OutputFormat::Table, Err(error)) => {
+            eprint!("{}", output::failure_table(workflow, error));
+            eprintln!("error: {:#}", error);
+        }
+    }
+
+    match outcome {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// Run (or plan) a workflow on every change until interrupted.
+///
+/// Results go to stdout as usual; what changed, diagnostics and the
+/// differences from the previous run go to stderr.
+fn watch(args: &RunArgs, options: Option<&RunOptions>) {
+    let inputs = args.inputs.clone();
+    let format = args.output;
+    let dry_run = options.is_none();
+    let history = options.is_some_and(|options| !options.no_history);
+
+    Watcher::new(&args.file)
+        .dry_run(dry_run)
+        .context(move || context(&inputs))
+        .watch(|update| {
+            let changed: Vec<String> = update
+                .changed
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect();
+            eprintln!("--- {} changed ---", changed.join(", "));
+
+            for diagnostic in &update.diagnostics {
+                eprintln!("{}", diagnostic);
+            }
+
+            let outcome = match update.outcome {
+                Outcome::LoadFailed(ref error) => {
+                    eprintln!("error: {:#}", error);
+                    None
+                }
+                Outcome::Invalid => {
+                    eprintln!("error: not run: the workflow is invalid");
+                    None
+                }
+                Outcome::Completed(ref result) => Some(Ok(&**result)),
+                Outcome::Failed(ref error) => Some(Err(error)),
+            };
+            if let (Some(outcome), Some(workflow)) = (outcome, &update.workflow) {
+                print_outcome(workflow, &args.inputs, outcome, format, dry_run);
+                match update.started_at {
+                    Some(started_at) if history => {
+                        record(workflow, &args.inputs, started_at, outcome)
+                    }
+                    _ => {}
+                }
+            }
+
+            match update.changes {
+                Some(ref changes) if changes.is_empty() => {
+                    eprintln!("No changes since the previous run")
+                }
+                Some(ref changes) => {
+                    eprintln!("Changes since the previous run:");
+                    for change in changes {
+                        eprintln!("  {}", change);
+                    }
+                }
+                None => {}
+            }
+            eprintln!();
+
+            ControlFlow::Continue(())
+        });
+}
+
+/// Validate a workflow and print its diagnostics.
+fn validate(file: &Path, manifests: Option<PathBuf>) -> Result<ExitCode> {
+    let workflow = fgp_workflow::load_file(file)?;
+
+    let diagnostics = match manifests {
+        Some(dir) => {
+            let manifests = Manifests::load_dir(&dir)
+                .with_context(|| format!("Failed to load manifests from {}", dir.display()))?;
+            workflow.validate_with(&manifests)
+        }
+        None => workflow.validate(),
+    };
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    if errors > 0 {
+        eprintln!(
+            "{}: {} error(s), {} warning(s)",
+            file.display(),
+            errors,
+            warnings
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+
+    println!("{}: ok ({} warning(s))", file.display(), warnings);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print a workflow's step graph.
+fn graph(file: &Path, format: GraphFormat) -> Result<ExitCode> {
+    let workflow = fgp_workflow::load_file(file)?;
+    let graph = Graph::from_workflow(&workflow);
+    match format {
+        GraphFormat::Text => print!("{}", graph),
+        GraphFormat::Dot => print!("{}", graph.to_dot()),
+        GraphFormat::Mermaid => print!("{}", graph.to_mermaid()),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Run scheduled workflows, printing a line per run.
+fn schedule(files: &[PathBuf], manifest: Option<&Path>) -> Result<ExitCode> {
+    let mut scheduler = Scheduler::new().on_run(|run| println!("{}", run));
+    if let Some(history) = default_history() {
+        scheduler = scheduler.history(history);
+    }
+
+    for file in files {
+        scheduler.add(fgp_workflow::load_file(file)?)?;
+    }
+    if let Some(manifest) = manifest {
+        scheduler.load_manifest(manifest)?;
+    }
+
+    let upcoming = scheduler.upcoming();
+    if upcoming.is_empty() {
+        anyhow::bail!("Nothing to schedule: give workflow files or a --manifest");
+    }
+    for (workflow, next) in upcoming {
+        match next {
+            Some(next) => eprintln!(
+                "{}: next run at {}",
+                workflow.name,
+                next.format("%Y-%m-%d %H:%M:%S")
+            ),
+            None => eprintln!("{}: never runs", workflow.name),
+        }
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopping = Arc::clone(&stop);
+    ctrlc::set_handler(move || {
+        eprintln!("Stopping after the runs in progress");
+        stopping.store(true, Ordering::Relaxed);
+    })
+    .context("Failed to handle Ctrl-C")?;
+
+    scheduler.run_until(&stop);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print recorded runs, or one run in full.
+fn history(
+    workflow: Option<&str>,
+    failed: bool,
+    limit: usize,
+    show: Option<&str>,
+) -> Result<ExitCode> {
+    let history = default_history().context("No home directory for the run history")?;
+
+    if let Some(id) = show {
+        let run = history
+            .get(id)?
+            .with_context(|| format!("No run with id '{}'", id))?;
+        println!("{}", serde_json::to_string_pretty(&run)?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut filter = RunFilter::new().limit(limit);
+    if let Some(workflow) = workflow {
+        filter = filter.workflow(workflow);
+    }
+    if failed {
+        filter = filter.status(RunStatus::Failed);
+    }
+
+    for run in history.list(&filter)? {
+        println!("{}", run);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Load the workflows in `dirs` and the user's workflow directory,
+/// reporting files that can't be used.
+fn registry(dirs: &[PathBuf]) -> Result<WorkflowRegistry> {
+    let registry = dirs
+        .iter()
+        .fold(WorkflowRegistry::new(), |registry, dir| registry.dir(dir))
+        .user_dir()
+        .load()?;
+
+    for problem in registry.problems() {
+        eprintln!("warning: {}", problem);
+    }
+    Ok(registry)
+}
+
+/// Print each workflow's name and description.
+fn list(dirs: &[PathBuf]) -> Result<ExitCode> {
+    let registry = registry(dirs)?;
+
+    let width = registry.iter().map(|w| w.name.len()).max().unwrap_or(0);
+    for workflow in registry.iter() {
+        let description = workflow.description.as_deref().unwrap_or_default();
+        println!("{:<width$}  {}", workflow.name, description, width = width);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Serve workflows until the server stops.
+fn serve(dirs: &[PathBuf], socket: Option<PathBuf>) -> Result<ExitCode> {
+    let registry = registry(dirs)?;
+    let socket = socket
+        .or_else(WorkflowService::default_socket)
+        .context("No home directory for the socket; pass --socket")?;
+
+    eprintln!(
+        "Serving {} workflow(s) on {}",
+        registry.len(),
+        socket.display()
+    );
+    let mut service = WorkflowService::new(registry);
+    if let Some(history) = default_history() {
+        service = service.history(history);
+    }
+    service.serve(&socket)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(
+            parse_input("account=work").unwrap(),
+            ("account".to_string(), Value::from("work"))
+        );
+        assert_eq!(
+            parse_input("limit=5").unwrap(),
+            ("limit".to_string(), Value::from(5))
+        );
+        assert_eq!(
+            parse_input("filter={\"unread\": true}").unwrap().1,
+            serde_json::json!({"unread": true})
+        );
+        assert_eq!(
+            parse_input("query=a=b").unwrap(),
+            ("query".to_string(), Value::from("a=b"))
+        );
+        assert!(parse_input("account").is_err());
+        assert!(parse_input("=x").is_err());
+    }
+
+    #[test]
+    fn test_cli_arguments() {
+        let cli = Cli::try_parse_from([
+            "fgp-workflow",
+            "plan",
+            "digest.yaml",
+            "-i",
+            "account=work",
+            "--input",
+            "limit=5",
+            "-o",
+            "json",
+        ])
+        .unwrap();
+
+        let Command::Plan(args) = cli.command else {
+            panic!("expected plan");
+        };
+        assert_eq!(args.file, PathBuf::from("digest.yaml"));
+        assert_eq!(args.inputs.len(), 2);
+        assert_eq!(args.output, OutputFormat::Json);
+    }
+
+    #[test]
+    fn test_reports_conflict_with_watch() {
+        for flag in ["--report", "--junit", "--markdown"] {
+            let parsed =
+                Cli::try_parse_from(["fgp-workflow", "run", "digest.yaml", "--watch", flag, "out"]);
+            assert!(parsed.is_err(), "{} was accepted with --watch", flag);
+        }
+
+        let cli = Cli::try_parse_from(["fgp-workflow", "run", "digest.yaml", "--junit", "out.xml"])
+            .unwrap();
+        let Command::Run { options, .. } = cli.command else {
+            panic!("expected run");
+        };
+        assert_eq!(options.junit, Some(PathBuf::from("out.xml")));
+    }
+}
@@ -0,0 +1,212 @@
+//! Printing execution results as tables.
+
+use fgp_workflow::{ExecutionResult, StepFailure, StepResult, Value, Workflow};
+
+/// Longest value shown in a table cell.
+const MAX_CELL: usize = 60;
+
+/// Table of a run's (or plan's) steps, followed by the result.
+pub fn table(workflow: &Workflow, result: &ExecutionResult, dry_run: bool) -> String {
+    let mut out = if dry_run {
+        format!(
+            "{}: plan of {} step(s)\n\n",
+            workflow.name,
+            result.step_results.len()
+        )
+    } else {
+        format!(
+            "{}: {} step(s) in {:.1} ms\n\n",
+            workflow.name,
+            result.step_results.len(),
+            result.total_ms
+        )
+    };
+
+    out.push_str(&steps_table(&result.step_results, dry_run, None));
+
+    if !dry_run {
+        out.push_str("\nResult:\n");
+        out.push_str(&pretty(&result.result));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Table of the steps that ran before a failure, ending with the failed step.
+pub fn failure_table(workflow: &Workflow, error: &anyhow::Error) -> String {
+    let Some(failure) = error.downcast_ref::<StepFailure>() else {
+        return String::new();
+    };
+
+    format!(
+        "{}: failed at step {}\n\n{}\n",
+        workflow.name,
+        failure.index,
+        steps_table(&failure.step_results, false, Some(failure))
+    )
+}
+
+/// Lay out step rows as aligned columns.
+///
+/// Runs show each step's duration and result, plans the params the step
+/// would be called with. Steps of sub-workflows are numbered `parent.child`.
+fn steps_table(steps: &[StepResult], dry_run: bool, failure: Option<&StepFailure>) -> String {
+    let header: &[&str] = if dry_run {
+        &["STEP", "RUNS", "PARAMS"]
+    } else {
+        &["STEP", "RUNS", "DURATION", "RESULT"]
+    };
+
+    let mut rows = vec![header.iter().map(|s| s.to_string()).collect::<Vec<_>>()];
+    add_rows(&mut rows, steps, "", dry_run);
+    if let Some(failure) = failure {
+        add_failure_rows(&mut rows, failure, "");
+    }
+
+    // Every column but the last is padded to its widest cell
+    let columns = header.len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| rows.iter().map(|r| r[c].chars().count()).max().unwrap_or(0))
+        .collect();
+
+    let mut out = String::new();
+    for row in rows {
+        let mut line = String::new();
+        for (c, cell) in row.iter().enumerate() {
+            if c + 1 == columns {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{:<width$}  ", cell, width = widths[c]));
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn add_rows(rows: &mut Vec<Vec<String>>, steps: &[StepResult], prefix: &str, dry_run: bool) {
+    for step in steps {
+        let number = format!("{}{}", prefix, step.index);
+        let runs = format!(
+            "{}{}",
+            "  ".repeat(prefix.matches('.').count()),
+            step.step.label()
+        );
+
+        rows.push(if dry_run {
+            vec![number.clone(), runs, cell(&step.params)]
+        } else {
+            vec![
+                number.clone(),
+                runs,
+                format!("{:.1} ms", step.duration_ms),
+                cell(&step.result),
+            ]
+        });
+
+        add_rows(rows, &step.children, &format!("{}.", number), dry_run);
+    }
+}
+
+/// Add the failed step's row, followed by the rows of its failed sub-workflow.
+fn add_failure_rows(rows: &mut Vec<Vec<String>>, failure: &StepFailure, prefix: &str) {
+    let number = format!("{}{}", prefix, failure.index);
+    rows.push(vec![
+        number.clone(),
+        format!(
+            "{}{}",
+            "  ".repeat(prefix.matches('.').count()),
+            failure.step.label()
+        ),
+        String::new(),
+        "FAILED".to_string(),
+    ]);
+
+    if let Some(ref child) = failure.child {
+        let prefix = format!("{}.", number);
+        add_rows(rows, &child.step_results, &prefix, false);
+        add_failure_rows(rows, child, &prefix);
+    }
+}
+
+/// Compact JSON for a table cell, shortened to [`MAX_CELL`] characters.
+fn cell(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() <= MAX_CELL {
+        return text;
+    }
+    let mut short: String = text.chars().take(MAX_CELL - 3).collect();
+    short.push_str("...");
+    short
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).expect("JSON values serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fgp_workflow::{dry_run, execute, Context, Step};
+
+    fn workflow() -> (Workflow, Context) {
+        let child = Workflow::new("child")
+            .add(Step::transform("{{ n }}"))
+            .build();
+        let workflow = Workflow::new("report")
+            .add(Step::transform(serde_json::json!({"n": 2})).output("data"))
+            .add(
+                Step::call("gmail", "gmail.send")
+                    .id("send")
+                    .with_param("count", "{{ data.n }}"),
+            )
+            .add(Step::workflow("child").with_param("n", 1))
+            .build();
+
+        (workflow, Context::new().with_workflow("child", child))
+    }
+
+    #[test]
+    fn test_plan_table() {
+        let (workflow, ctx) = workflow();
+        let plan = dry_run(&workflow, ctx).unwrap();
+
+        assert_eq!(
+            table(&workflow, &plan, true),
+            "report: plan of 3 step(s)
+
+STEP  RUNS            PARAMS
+0     transform       {}
+1     gmail.send      {\"count\":2}
+2     workflow child  {\"n\":1}
+2.0     transform     {}
+"
+        );
+    }
+
+    #[test]
+    fn test_failure_output() {
+        let workflow = Workflow::new("report")
+            .add(Step::transform(serde_json::json!({"n": 2})))
+            .add(Step::workflow("missing"))
+            .build();
+        let err = execute(&workflow).unwrap_err();
+
+        let table = failure_table(&workflow, &err);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "report: failed at step 1");
+        assert_eq!(lines[2], "STEP  RUNS              DURATION  RESULT");
+        assert!(lines[3].starts_with("0     transform         "));
+        assert!(lines[3].ends_with(" ms    {\"n\":2}"));
+        assert_eq!(lines[4], "1     workflow missing            FAILED");
+    }
+
+    #[test]
+    fn test_cell_is_shortened() {
+        let long = Value::from("x".repeat(100));
+        assert_eq!(cell(&long).chars().count(), MAX_CELL);
+        assert!(cell(&long).ends_with("..."));
+    }
+}
@@ -1,34 +1,155 @@
 //! Execution context for workflow variables.
 
+use crate::param::Param;
+use crate::secrets::{EnvSecretProvider, Redactor, SecretProvider};
+use crate::workflow::TemplateMode;
+use crate::Workflow;
 use anyhow::{Context as _, Result};
-use handlebars::Handlebars;
+use handlebars::template::{Parameter, Template, TemplateElement};
+use handlebars::{Handlebars, Path as HbPath, PathSeg};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Execution context that holds variables and results.
-#[derive(Debug, Default)]
+///
+/// Serializes as its variables, results, step records and depth. The
+/// secret provider, the redactor's secret values, registered workflows and
+/// the template engine are left out; a deserialized context gets the
+/// defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Context {
     /// Named variables from step outputs
-    variables: HashMap<String, Value>,
+    #[serde(default)]
+    variables: IndexMap<String, Value>,
 
     /// Results from each step (accessed via $prev)
+    #[serde(default)]
     results: Vec<Value>,
 
+    /// Records of steps with an id (accessed via steps.<id>)
+    #[serde(default)]
+    steps: IndexMap<String, Value>,
+
+    /// How [`Context::resolve`] recognizes template strings
+    #[serde(skip)]
+    template_mode: TemplateMode,
+
+    /// Source of `secrets.*` values
+    #[serde(skip, default = "default_secrets")]
+    secrets: Arc<dyn SecretProvider>,
+
+    /// Masks secret values handed out to templates
+    #[serde(skip)]
+    redactor: Redactor,
+
+    /// Workflows that workflow steps can run by name
+    #[serde(skip)]
+    workflows: Arc<IndexMap<String, Workflow>>,
+
+    /// Number of enclosing workflow steps
+    #[serde(default)]
+    depth: usize,
+
     /// Handlebars template engine
     #[allow(dead_code)]
+    #[serde(skip)]
     handlebars: Handlebars<'static>,
 }
 
+fn default_secrets() -> Arc<dyn SecretProvider> {
+    Arc::new(EnvSecretProvider::new())
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Context {
     /// Create a new empty context.
     pub fn new() -> Self {
         Self {
-            variables: HashMap::new(),
+            variables: IndexMap::new(),
             results: Vec::new(),
+            steps: IndexMap::new(),
+            template_mode: TemplateMode::Auto,
+            secrets: default_secrets(),
+            redactor: Redactor::new(),
+            workflows: Arc::new(IndexMap::new()),
+            depth: 0,
             handlebars: Handlebars::new(),
         }
     }
 
+    /// Create an empty context for a sub-workflow.
+    ///
+    /// The child shares secrets, the redactor and registered workflows, but
+    /// none of the variables or results.
+    pub(crate) fn child(&self) -> Self {
+        Self {
+            secrets: self.secrets.clone(),
+            redactor: self.redactor.clone(),
+            workflows: self.workflows.clone(),
+            depth: self.depth + 1,
+            ..Self::new()
+        }
+    }
+
+    /// Use a secret provider for `secrets.*` template values.
+    ///
+    /// Defaults to [`EnvSecretProvider`].
+    pub fn with_secrets<P: SecretProvider + 'static>(mut self, provider: P) -> Self {
+        self.secrets = Arc::new(provider);
+        self
+    }
+
+    /// Set how [`resolve`](Self::resolve) recognizes template strings.
+    pub fn set_template_mode(&mut self, mode: TemplateMode) {
+        self.template_mode = mode;
+    }
+
+    /// Register a workflow that workflow steps can run by name.
+    pub fn with_workflow(mut self, name: &str, workflow: Workflow) -> Self {
+        Arc::make_mut(&mut self.workflows).insert(name.to_string(), workflow);
+        self
+    }
+
+    /// Get a registered workflow by name.
+    pub fn workflow(&self, name: &str) -> Option<&Workflow> {
+        self.workflows.get(name)
+    }
+
+    /// Number of enclosing workflow steps (0 for a top-level run).
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Redactor that masks the secret values used so far.
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Mask secret values in all variables, results and step records.
+    pub fn redact(&mut self) {
+        let redactor = self.redactor.clone();
+        if redactor.is_empty() {
+            return;
+        }
+
+        for value in self.variables.values_mut() {
+            *value = redactor.redact(value);
+        }
+        for value in self.results.iter_mut() {
+            *value = redactor.redact(value);
+        }
+        for value in self.steps.values_mut() {
+            *value = redactor.redact(value);
+        }
+    }
+
     /// Set a variable.
     pub fn set(&mut self, name: &str, value: Value) {
         self.variables.insert(name.to_string(), value);
@@ -39,6 +160,11 @@ impl Context {
         self.variables.get(name)
     }
 
+    /// Names of the variables set so far.
+    pub(crate) fn variable_names(&self) -> Vec<&str> {
+        self.variables.keys().map(String::as_str).collect()
+    }
+
     /// Push a result onto the results stack.
     pub fn push_result(&mut self, value: Value) {
         self.results.push(value);
@@ -54,44 +180,60 @@ impl Context {
         &self.results
     }
 
+    /// Record a step by id.
+    ///
+    /// The record is an object with `result`, `status`, `duration_ms` and
+    /// `params` fields, available to templates as `steps.<id>`.
+    pub fn set_step(&mut self, id: &str, record: Value) {
+        self.steps.insert(id.to_string(), record);
+    }
+
+    /// Get the record of a step by id.
+    pub fn step(&self, id: &str) -> Option<&Value> {
+        self.steps.get(id)
+    }
+
     /// Resolve a value, expanding any templates.
     ///
-    /// Templates are marked with `__template__` key and use Handlebars syntax.
+    /// The value is read as a serialized [`Param`]: values marked with
+    /// `__template__` are Handlebars templates, values marked with `__raw__`
+    /// are returned unchanged. In [`TemplateMode::Auto`], strings containing
+    /// `{{ }}` are templates too.
     pub fn resolve(&self, value: &Value) -> Result<Value> {
-        match value {
-            Value::Object(map) => {
-                // Check if this is a template
-                if let Some(Value::String(template_str)) = map.get("__template__") {
-                    return self.render_template(template_str);
-                }
+        self.resolve_param(&Param::parse_with(value.clone(), self.template_mode))
+    }
 
-                // Recursively resolve object values
+    /// Resolve a step parameter, rendering its templates.
+    pub fn resolve_param(&self, param: &Param) -> Result<Value> {
+        match param {
+            Param::Literal(value) => Ok(value.clone()),
+            Param::Template(template) => self.render_template(template),
+            Param::Object(fields) => {
                 let mut result = Map::new();
-                for (k, v) in map {
-                    result.insert(k.clone(), self.resolve(v)?);
+                for (k, v) in fields {
+                    result.insert(k.clone(), self.resolve_param(v)?);
                 }
                 Ok(Value::Object(result))
             }
-            Value::Array(arr) => {
-                let resolved: Result<Vec<Value>> = arr.iter().map(|v| self.resolve(v)).collect();
+            Param::Array(items) => {
+                let resolved: Result<Vec<Value>> =
+                    items.iter().map(|v| self.resolve_param(v)).collect();
                 Ok(Value::Array(resolved?))
             }
-            Value::String(s) => {
-                // Check for inline templates {{ ... }}
-                if s.contains("{{") && s.contains("}}") {
-                    self.render_template(s)
-                } else {
-                    Ok(value.clone())
-                }
-            }
-            _ => Ok(value.clone()),
         }
     }
 
     /// Render a Handlebars template string.
+    ///
+    /// A template that is a single `{{ variable.path }}` expression naming an
+    /// object or array returns the value itself, so it keeps its shape.
+    /// Other output is parsed as JSON if it can be, and kept as a string
+    /// otherwise.
     fn render_template(&self, template: &str) -> Result<Value> {
         let mut hb = Handlebars::new();
         hb.set_strict_mode(false);
+        // Rendered values are data, not HTML
+        hb.register_escape_fn(handlebars::no_escape);
 
         // Build context data
         let mut data = self.variables.clone();
@@ -106,6 +248,39 @@ impl Context {
         data.insert("$results".to_string(), Value::Array(self.results.clone()));
         data.insert("results".to_string(), Value::Array(self.results.clone()));
 
+        // Add step records by id
+        let steps: Map<String, Value> = self
+            .steps
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        data.insert("steps".to_string(), Value::Object(steps));
+
+        // Add env.* and the secrets.* the template references
+        let paths = template_paths(template);
+        if paths.iter().any(|path| path[0] == "env") {
+            let env: Map<String, Value> = std::env::vars()
+                .map(|(k, v)| (k, Value::String(v)))
+                .collect();
+            data.insert("env".to_string(), Value::Object(env));
+        }
+        let secrets: Vec<&str> = paths
+            .iter()
+            .filter(|path| path[0] == "secrets" && path.len() > 1)
+            .map(|path| path[1].as_str())
+            .collect();
+        if !secrets.is_empty() {
+            data.insert("secrets".to_string(), self.fetch_secrets(&secrets)?);
+        }
+
+        // Objects and arrays would render as `[object]`; everything else
+        // renders as before
+        if let Some(path) = single_expression(template) {
+            if let Some(value @ (Value::Object(_) | Value::Array(_))) = lookup(&data, path) {
+                return Ok(value.clone());
+            }
+        }
+
         let rendered = hb
             .render_template(template, &data)
             .context("Failed to render template")?;
@@ -117,7 +292,31 @@ impl Context {
         }
     }
 
+    /// Fetch secrets by name.
+    fn fetch_secrets(&self, names: &[&str]) -> Result<Value> {
+        let mut secrets = Map::new();
+
+        for &name in names {
+            if secrets.contains_key(name) {
+                continue;
+            }
+
+            let value = self
+                .secrets
+                .get(name)
+                .with_context(|| format!("Failed to read secret '{}'", name))?
+                .with_context(|| format!("Secret '{}' not found", name))?;
+
+            self.redactor.add(&value);
+            secrets.insert(name.to_string(), Value::String(value));
+        }
+
+        Ok(Value::Object(secrets))
+    }
+
     /// Get all variables as a JSON object.
+    ///
+    /// Variables and step records appear in the order they were set.
     pub fn as_json(&self) -> Value {
         let mut data = Map::new();
 
@@ -131,10 +330,125 @@ impl Context {
 
         data.insert("$results".to_string(), Value::Array(self.results.clone()));
 
+        let steps: Map<String, Value> = self
+            .steps
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        data.insert("$steps".to_string(), Value::Object(steps));
+
         Value::Object(data)
     }
 }
 
+/// Extract the path from a template consisting of a single `{{ path }}` expression.
+fn single_expression(template: &str) -> Option<&str> {
+    let inner = template
+        .trim()
+        .strip_prefix("{{")?
+        .strip_suffix("}}")?
+        .trim();
+
+    let is_path = !inner.is_empty()
+        && inner
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '$'));
+
+    is_path.then_some(inner)
+}
+
+/// Collect the variable paths a template references, as their named segments.
+///
+/// Only `{{ }}` expressions count, so literal text such as
+/// `docs at secrets.example.com` references nothing. A template that fails
+/// to parse references nothing; rendering reports the error.
+fn template_paths(template: &str) -> Vec<Vec<String>> {
+    let mut paths = Vec::new();
+    if let Ok(compiled) = Template::compile(template) {
+        collect_template_paths(&compiled, &mut paths);
+    }
+    paths
+}
+
+fn collect_template_paths(template: &Template, paths: &mut Vec<Vec<String>>) {
+    for element in &template.elements {
+        collect_element_paths(element, paths);
+    }
+}
+
+fn collect_element_paths(element: &TemplateElement, paths: &mut Vec<Vec<String>>) {
+    match element {
+        TemplateElement::Expression(helper)
+        | TemplateElement::HtmlExpression(helper)
+        | TemplateElement::HelperBlock(helper) => {
+            collect_parameter_paths(&helper.name, paths);
+            for param in helper.params.iter().chain(helper.hash.values()) {
+                collect_parameter_paths(param, paths);
+            }
+            for nested in helper.template.iter().chain(helper.inverse.iter()) {
+                collect_template_paths(nested, paths);
+            }
+        }
+        TemplateElement::DecoratorExpression(decorator)
+        | TemplateElement::DecoratorBlock(decorator)
+        | TemplateElement::PartialExpression(decorator)
+        | TemplateElement::PartialBlock(decorator) => {
+            for param in decorator.params.iter().chain(decorator.hash.values()) {
+                collect_parameter_paths(param, paths);
+            }
+            if let Some(nested) = &decorator.template {
+                collect_template_paths(nested, paths);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_parameter_paths(param: &Parameter, paths: &mut Vec<Vec<String>>) {
+    match param {
+        Parameter::Name(name) => paths.push(vec![name.clone()]),
+        Parameter::Path(HbPath::Relative((segments, _))) => {
+            let named: Vec<String> = segments
+                .iter()
+                .map_while(|segment| match segment {
+                    PathSeg::Named(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !named.is_empty() {
+                paths.push(named);
+            }
+        }
+        Parameter::Subexpression(subexpression) => {
+            collect_element_paths(&subexpression.element, paths);
+        }
+        _ => {}
+    }
+}
+
+/// Look up a dotted path (e.g. `emails.0.subject`) in template data.
+fn lookup<'a>(data: &'a IndexMap<String, Value>, path: &str) -> Option<&'a Value> {
+    let (head, rest) = path.split_once('.').unwrap_or((path, ""));
+    lookup_path(data.get(head)?, rest)
+}
+
+/// Look up a dotted path (e.g. `messages.0.id`) in a value.
+///
+/// An empty path returns the value itself.
+pub(crate) fn lookup_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        current = match current {
+            Value::Object(map) => map.get(segment)?,
+            Value::Array(arr) => arr.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,6 +475,22 @@ mod tests {
         assert_eq!(ctx.prev().unwrap().get("id"), Some(&Value::from(2)));
     }
 
+    #[test]
+    fn test_step_records() {
+        let mut ctx = Context::new();
+        ctx.set_step(
+            "inbox",
+            serde_json::json!({"result": {"count": 3}, "status": "success"}),
+        );
+
+        let value = Value::String("{{ steps.inbox.result.count }} new".to_string());
+        assert_eq!(
+            ctx.resolve(&value).unwrap(),
+            Value::String("3 new".to_string())
+        );
+        assert!(ctx.step("missing").is_none());
+    }
+
     #[test]
     fn test_template_resolution() {
         let mut ctx = Context::new();
@@ -185,4 +515,171 @@ mod tests {
             Value::String("Visit https://example.com".to_string())
         );
     }
+
+    #[test]
+    fn test_raw_value() {
+        let mut ctx = Context::new();
+        ctx.set("name", Value::String("Alice".to_string()));
+
+        let raw = serde_json::json!({"__raw__": {"body": "Hello {{ name }}"}});
+        assert_eq!(
+            ctx.resolve(&raw).unwrap(),
+            serde_json::json!({"body": "Hello {{ name }}"})
+        );
+    }
+
+    #[test]
+    fn test_explicit_template_mode() {
+        let mut ctx = Context::new();
+        ctx.set("name", Value::String("Alice".to_string()));
+        ctx.set_template_mode(TemplateMode::Explicit);
+
+        let inline = Value::String("Hello {{ name }}".to_string());
+        assert_eq!(ctx.resolve(&inline).unwrap(), inline);
+
+        let marked = serde_json::json!({"__template__": "Hello {{ name }}"});
+        assert_eq!(
+            ctx.resolve(&marked).unwrap(),
+            Value::String("Hello Alice".to_string())
+        );
+    }
+
+    #[test]
+    fn test_single_expression_keeps_value() {
+        let mut ctx = Context::new();
+        ctx.set(
+            "emails",
+            serde_json::json!({"messages": [{"id": "a"}, {"id": "b"}], "count": "2"}),
+        );
+
+        let messages = ctx
+            .resolve(&Value::String("{{ emails.messages }}".to_string()))
+            .unwrap();
+        assert_eq!(messages, serde_json::json!([{"id": "a"}, {"id": "b"}]));
+
+        let count = ctx
+            .resolve(&Value::String("{{ emails.count }}".to_string()))
+            .unwrap();
+        assert_eq!(count, Value::from(2));
+    }
+
+    #[test]
+    fn test_template_not_html_escaped() {
+        let mut ctx = Context::new();
+        ctx.set("name", Value::String("O'Brien & <Co>".to_string()));
+
+        let value = Value::String("Hi {{ name }}".to_string());
+        let resolved = ctx.resolve(&value).unwrap();
+
+        assert_eq!(resolved, Value::String("Hi O'Brien & <Co>".to_string()));
+    }
+
+    #[test]
+    fn test_env_template() {
+        std::env::set_var("FGP_TEST_CONTEXT_ACCOUNT", "acct-1");
+        let ctx = Context::new();
+
+        let value = Value::String("{{ env.FGP_TEST_CONTEXT_ACCOUNT }}".to_string());
+        assert_eq!(
+            ctx.resolve(&value).unwrap(),
+            Value::String("acct-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_secret_template_and_redaction() {
+        use crate::secrets::MemorySecretProvider;
+
+        let mut ctx =
+            Context::new().with_secrets(MemorySecretProvider::new().with("token", "s3cret"));
+
+        let value = Value::String("Bearer {{ secrets.token }}".to_string());
+        let resolved = ctx.resolve(&value).unwrap();
+        assert_eq!(resolved, Value::String("Bearer s3cret".to_string()));
+
+        ctx.set("echo", serde_json::json!({"header": "Bearer s3cret"}));
+        ctx.redact();
+        assert_eq!(
+            ctx.get("echo"),
+            Some(&serde_json::json!({"header": "Bearer ***"}))
+        );
+    }
+
+    #[test]
+    fn test_missing_secret() {
+        use crate::secrets::MemorySecretProvider;
+
+        let ctx = Context::new().with_secrets(MemorySecretProvider::new());
+
+        let value = Value::String("{{ secrets.nope }}".to_string());
+        let err = ctx.resolve(&value).unwrap_err();
+        assert!(err.to_string().contains("Secret 'nope' not found"));
+    }
+
+    #[test]
+    fn test_literal_namespace_text() {
+        use crate::secrets::MemorySecretProvider;
+
+        let mut ctx = Context::new().with_secrets(MemorySecretProvider::new());
+        ctx.set("input", serde_json::json!({"env": {"x": "work"}}));
+
+        let value =
+            Value::String("see docs at secrets.example.com for {{ input.env.x }}".to_string());
+        assert_eq!(
+            ctx.resolve(&value).unwrap(),
+            Value::String("see docs at secrets.example.com for work".to_string())
+        );
+    }
+
+    #[test]
+    fn test_as_json_order() {
+        let mut ctx = Context::new();
+        for name in ["zeta", "alpha", "mid"] {
+            ctx.set(name, Value::from(name));
+        }
+        ctx.set_step("second", serde_json::json!({}));
+        ctx.set_step("first", serde_json::json!({}));
+
+        let json = ctx.as_json();
+        let keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["zeta", "alpha", "mid", "$results", "$steps"]);
+
+        let steps: Vec<&String> = json["$steps"].as_object().unwrap().keys().collect();
+        assert_eq!(steps, ["second", "first"]);
+    }
+
+    #[test]
+    fn test_serialize() {
+        use crate::secrets::MemorySecretProvider;
+
+        let mut ctx =
+            Context::new().with_secrets(MemorySecretProvider::new().with("token", "s3cret"));
+        ctx.resolve(&Value::from("{{ secrets.token }}")).unwrap();
+        ctx.set("emails", serde_json::json!([{"subject": "Hi"}]));
+        ctx.push_result(Value::from(1));
+        ctx.set_step("inbox", serde_json::json!({"status": "success"}));
+
+        let json = serde_json::to_value(&ctx).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "variables": {"emails": [{"subject": "Hi"}]},
+                "results": [1],
+                "steps": {"inbox": {"status": "success"}},
+                "depth": 0,
+            })
+        );
+
+        let loaded: Context = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.get("emails"), ctx.get("emails"));
+        assert_eq!(loaded.prev(), Some(&Value::from(1)));
+        assert_eq!(loaded.step("inbox"), ctx.step("inbox"));
+        assert!(loaded.redactor().is_empty());
+        assert_eq!(
+            loaded
+                .resolve(&Value::from("{{ emails.0.subject }}"))
+                .unwrap(),
+            "Hi"
+        );
+    }
 }
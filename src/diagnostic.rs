@@ -0,0 +1,492 @@
+//! Source-located diagnostics for workflow files.
+//!
+//! Errors found while loading a workflow carry the path of the offending
+//! node in the document (e.g. `steps.3.method`) and, when the source is
+//! available, its file, line and column:
+//!
+//! ```text
+//! digest.yaml:7:13: error: Step 1 has invalid method name 'gmail inbox'
+//!   |
+//! 7 |     method: "gmail inbox"
+//!   |             ^
+//! ```
+//!
+//! The first line follows the `file:line:column: message` convention that
+//! editors and CI annotators recognize. Loading functions return these as
+//! [`anyhow::Error`]s; use `err.downcast_ref::<Diagnostic>()` to get at the
+//! fields.
+
+use crate::Format;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+/// Key recording where an included step came from, removed before parsing.
+pub(crate) const SOURCE_KEY: &str = "__source__";
+
+/// A line and column in a source file (both 1-based).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// Line number
+    pub line: usize,
+
+    /// Column number
+    pub column: usize,
+}
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The workflow cannot load or will fail when run.
+    Error,
+
+    /// The workflow runs, but probably not as intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A problem found in a workflow document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+
+    /// Description of the problem
+    pub message: String,
+
+    /// Dotted path of the offending node (e.g. `steps.3.method`); empty for
+    /// the whole document
+    pub path: String,
+
+    /// File the problem is in, if the workflow was loaded from a file
+    pub file: Option<PathBuf>,
+
+    /// Position of the problem, if the source could be mapped
+    pub location: Option<Location>,
+
+    /// Source line at the location
+    pub snippet: Option<String>,
+}
+
+impl Diagnostic {
+    /// Create an error for a node in the document.
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            path: path.into(),
+            file: None,
+            location: None,
+            snippet: None,
+        }
+    }
+
+    /// Set the severity.
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Whether this is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Point the diagnostic at a position in a source text.
+    fn at(mut self, text: &str, location: Location) -> Self {
+        self.snippet = text
+            .lines()
+            .nth(location.line.saturating_sub(1))
+            .map(str::to_string);
+        self.location = Some(location);
+        self
+    }
+
+    /// Create a diagnostic for a syntax error at a position in a source text.
+    pub(crate) fn syntax(text: &str, message: impl Into<String>, location: Location) -> Self {
+        Self::new("", message).at(text, location)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        if let Some(location) = self.location {
+            write!(f, "{}:{}:", location.line, location.column)?;
+        }
+        if self.file.is_some() || self.location.is_some() {
+            f.write_str(" ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)?;
+
+        if let (Some(location), Some(snippet)) = (self.location, &self.snippet) {
+            let number = location.line.to_string();
+            let gutter = " ".repeat(number.len());
+            let indent: String = snippet
+                .chars()
+                .take(location.column.saturating_sub(1))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            write!(
+                f,
+                "\n{gutter} |\n{number} | {snippet}\n{gutter} | {indent}^"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Set the file of a diagnostic error, or add the file as context to any
+/// other error.
+pub(crate) fn in_file(err: anyhow::Error, path: &Path) -> anyhow::Error {
+    match err.downcast::<Diagnostic>() {
+        Ok(mut diagnostic) => {
+            diagnostic.file.get_or_insert_with(|| path.to_path_buf());
+            diagnostic.into()
+        }
+        Err(err) => err.context(format!("Failed to parse workflow file: {}", path.display())),
+    }
+}
+
+/// Where a step came from: a file and the step's path in that file.
+#[derive(Debug, Clone)]
+pub(crate) struct Origin {
+    pub file: PathBuf,
+    pub path: String,
+}
+
+impl Origin {
+    /// Read an origin recorded under [`SOURCE_KEY`].
+    pub(crate) fn from_value(value: &serde_yaml::Value) -> Option<Self> {
+        let file = value.get(0)?.as_str()?;
+        let path = value.get(1)?.as_str()?;
+        Some(Self {
+            file: PathBuf::from(file),
+            path: path.to_string(),
+        })
+    }
+
+    /// Encode an origin to record under [`SOURCE_KEY`].
+    pub(crate) fn to_value(file: &Path, path: &str) -> serde_yaml::Value {
+        serde_yaml::Value::Sequence(vec![
+            file.display().to_string().into(),
+            path.to_string().into(),
+        ])
+    }
+}
+
+/// Maps document paths to positions in the source a workflow was read from.
+pub(crate) struct Locator {
+    /// File of the document, if read from a file
+    file: Option<PathBuf>,
+
+    /// Source text, if read from a string
+    text: Option<(String, Format)>,
+
+    /// Origins of the document's steps, by index
+    pub origins: Vec<Option<Origin>>,
+}
+
+impl Locator {
+    /// Locate paths in a source string.
+    pub(crate) fn text(text: &str, format: Format) -> Self {
+        Self {
+            file: None,
+            text: Some((text.to_string(), format)),
+            origins: Vec::new(),
+        }
+    }
+
+    /// Locate paths in the file a workflow was loaded from, if any.
+    pub(crate) fn workflow(workflow: &crate::Workflow) -> Self {
+        Self {
+            file: workflow.source.clone(),
+            text: None,
+            origins: workflow.origins.clone(),
+        }
+    }
+
+    /// Locate paths in a file, read again only if a diagnostic needs it.
+    pub(crate) fn file(path: &Path) -> Self {
+        Self {
+            file: Some(path.to_path_buf()),
+            text: None,
+            origins: Vec::new(),
+        }
+    }
+
+    /// Fill in the file, location and snippet of a diagnostic.
+    pub(crate) fn locate(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        let mut file = self.file.clone();
+        let mut path = diagnostic.path.clone();
+
+        // Steps may come from included files
+        if let Some(rest) = path.strip_prefix("steps.") {
+            let (index, rest) = rest.split_once('.').unwrap_or((rest, ""));
+            let origin = index
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| self.origins.get(i)?.as_ref());
+            if let Some(origin) = origin {
+                file = Some(origin.file.clone());
+                path = join(&origin.path, rest);
+            }
+        }
+
+        let source = if file == self.file && self.text.is_some() {
+            self.text.clone()
+        } else {
+            file.as_ref().and_then(|file| {
+                let format = Format::from_path(file).unwrap_or(Format::Yaml);
+                std::fs::read_to_string(file)
+                    .ok()
+                    .map(|text| (text, format))
+            })
+        };
+
+        diagnostic.file = file;
+        if let Some((text, format)) = source {
+            if let Some(location) = SourceMap::parse(&text, format).and_then(|m| m.find(&path)) {
+                diagnostic = diagnostic.at(&text, location);
+            }
+        }
+
+        diagnostic
+    }
+}
+
+/// Join two dotted paths.
+fn join(parent: &str, child: &str) -> String {
+    match (parent.is_empty(), child.is_empty()) {
+        (true, _) => child.to_string(),
+        (_, true) => parent.to_string(),
+        _ => format!("{}.{}", parent, child),
+    }
+}
+
+/// Positions of the nodes of a YAML (or JSON) document, by dotted path.
+struct SourceMap {
+    nodes: HashMap<String, Location>,
+}
+
+impl SourceMap {
+    /// Map a source text; `None` if it can't be parsed as YAML.
+    ///
+    /// JSON is read as YAML; TOML sources are not mapped.
+    fn parse(text: &str, format: Format) -> Option<Self> {
+        if format == Format::Toml {
+            return None;
+        }
+
+        let mut builder = SourceMapBuilder::default();
+        Parser::new_from_str(text).load(&mut builder, false).ok()?;
+
+        Some(Self {
+            nodes: builder.nodes,
+        })
+    }
+
+    /// Position of a node, or of its closest ancestor present in the source.
+    fn find(&self, path: &str) -> Option<Location> {
+        let mut path = path;
+        loop {
+            if let Some(location) = self.nodes.get(path) {
+                return Some(*location);
+            }
+            path = match path.rsplit_once('.') {
+                Some((parent, _)) => parent,
+                None if !path.is_empty() => "",
+                None => return None,
+            };
+        }
+    }
+}
+
+/// A collection being read while building a [`SourceMap`].
+enum Frame {
+    Sequence {
+        path: String,
+        index: usize,
+    },
+    Mapping {
+        path: String,
+        key: Option<String>,
+        empty: bool,
+    },
+}
+
+#[derive(Default)]
+struct SourceMapBuilder {
+    stack: Vec<Frame>,
+    nodes: HashMap<String, Location>,
+}
+
+impl SourceMapBuilder {
+    /// If the next node is a mapping key, return the mapping's key slot.
+    ///
+    /// Block mappings start at their first key, but the parser marks them
+    /// at the first colon, so the first key's mark replaces the mapping's.
+    fn pending_key(&mut self, mark: Marker) -> Option<&mut Option<String>> {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { path, key, empty }) if key.is_none() => {
+                if std::mem::take(empty) {
+                    self.nodes.insert(path.clone(), location(mark));
+                }
+                Some(key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Path of the next value node, advancing the enclosing collection.
+    fn next_path(&mut self) -> String {
+        match self.stack.last_mut() {
+            Some(Frame::Sequence { path, index }) => {
+                *index += 1;
+                join(path, &(*index - 1).to_string())
+            }
+            Some(Frame::Mapping { path, key, .. }) => join(path, &key.take().unwrap_or_default()),
+            None => String::new(),
+        }
+    }
+
+    /// Record a value node and return its path.
+    fn record(&mut self, mark: Marker) -> String {
+        let path = self.next_path();
+        self.nodes.entry(path.clone()).or_insert(location(mark));
+        path
+    }
+}
+
+/// Convert a parser mark (with a 0-based column) to a location.
+fn location(mark: Marker) -> Location {
+    Location {
+        line: mark.line(),
+        column: mark.col() + 1,
+    }
+}
+
+impl MarkedEventReceiver for SourceMapBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                if let Some(key) = self.pending_key(mark) {
+                    *key = Some(value);
+                } else {
+                    self.record(mark);
+                }
+            }
+            Event::Alias(_) => {
+                if let Some(key) = self.pending_key(mark) {
+                    *key = Some(String::new());
+                } else {
+                    self.record(mark);
+                }
+            }
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                // Collections used as mapping keys are not addressable
+                let path = match self.pending_key(mark) {
+                    Some(key) => {
+                        *key = Some(String::new());
+                        "?".to_string()
+                    }
+                    None => self.record(mark),
+                };
+                self.stack.push(match event {
+                    Event::SequenceStart(..) => Frame::Sequence { path, index: 0 },
+                    _ => Frame::Mapping {
+                        path,
+                        key: None,
+                        empty: true,
+                    },
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = r#"name: digest
+steps:
+  - service: gmail
+    method: gmail.inbox
+  - service: browser
+    method: "browser open"
+    params:
+      url: "{{ prev.0.url }}"
+"#;
+
+    #[test]
+    fn test_source_map_paths() {
+        let map = SourceMap::parse(YAML, Format::Yaml).unwrap();
+
+        assert_eq!(map.find("name"), Some(Location { line: 1, column: 7 }));
+        assert_eq!(map.find("steps.1"), Some(Location { line: 5, column: 5 }));
+        assert_eq!(
+            map.find("steps.1.method"),
+            Some(Location {
+                line: 6,
+                column: 13
+            })
+        );
+        assert_eq!(
+            map.find("steps.1.params.url"),
+            Some(Location {
+                line: 8,
+                column: 12
+            })
+        );
+        // Missing nodes fall back to their closest ancestor
+        assert_eq!(map.find("steps.0.params"), map.find("steps.0"));
+    }
+
+    #[test]
+    fn test_source_map_json() {
+        let json = "{\n  \"name\": \"x\",\n  \"steps\": [\n    {\"service\": \"a\"}\n  ]\n}";
+        let map = SourceMap::parse(json, Format::Json).unwrap();
+
+        assert_eq!(
+            map.find("steps.0.service"),
+            Some(Location {
+                line: 4,
+                column: 17
+            })
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let diagnostic = Locator::text(YAML, Format::Yaml).locate(Diagnostic::new(
+            "steps.1.method",
+            "Step 1 has invalid method name 'browser open'",
+        ));
+
+        assert_eq!(
+            diagnostic.to_string(),
+            "6:13: error: Step 1 has invalid method name 'browser open'\n  \
+             |\n6 |     method: \"browser open\"\n  |             ^"
+        );
+    }
+}
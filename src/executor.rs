@@ -1,13 +1,26 @@
 //! Workflow execution engine.
 
-use crate::{Context, Step, Workflow};
+use crate::secrets::Redactor;
+use crate::step::{is_template, is_valid_name};
+use crate::validate::{for_each_template, references};
+use crate::{Context, Format, Param, Step, Workflow};
 use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// Maximum nesting of workflow steps, to stop runaway recursion.
+pub const MAX_WORKFLOW_DEPTH: usize = 16;
 
 /// Result of workflow execution.
-#[derive(Debug)]
+///
+/// Secret values used during execution are masked in step results and the
+/// final context.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResult {
-    /// Final result (last step's output)
+    /// Final result (declared outputs, or the last step's output)
     pub result: Value,
 
     /// All step results
@@ -21,7 +34,7 @@ pub struct ExecutionResult {
 }
 
 /// Result of a single step execution.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StepResult {
     /// Step index (0-based)
     pub index: usize,
@@ -29,13 +42,84 @@ pub struct StepResult {
     /// Step that was executed
     pub step: Step,
 
+    /// Resolved parameters the step was called with
+    pub params: Value,
+
     /// Result of the step
     pub result: Value,
 
     /// Execution time in milliseconds
     pub duration_ms: f64,
+
+    /// Steps run by a workflow step
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<StepResult>,
 }
 
+/// Context of an error raised while running a step.
+///
+/// Get it from an execution error with `downcast_ref`; it identifies the
+/// failed step and holds the results of the steps before it. When the
+/// failed step is a workflow step whose workflow failed at a step, `child`
+/// describes that failure the same way:
+///
+/// ```rust
+/// use fgp_workflow::{execute, Step, StepFailure, Workflow};
+///
+/// let workflow = Workflow::new("example")
+///     .add(Step::transform(1))
+///     .add(Step::workflow("missing"))
+///     .build();
+///
+/// let err = execute(&workflow).unwrap_err();
+/// let failure = err.downcast_ref::<StepFailure>().unwrap();
+/// assert_eq!(failure.index, 1);
+/// assert_eq!(failure.step_results.len(), 1);
+/// assert_eq!(
+///     err.to_string(),
+///     "Step 1 (workflow missing) failed: Workflow 'missing' is not registered"
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepFailure {
+    /// Index of the failed step (0-based)
+    pub index: usize,
+
+    /// Step that failed
+    pub step: Step,
+
+    /// Results of the steps that completed before it
+    pub step_results: Vec<StepResult>,
+
+    /// Resolved params of the failed step, with secret values masked, if it
+    /// got as far as resolving them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+
+    /// Why the step failed, with secret values masked
+    pub error: String,
+
+    /// How the workflow run by the failed step failed, if it failed at a step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child: Option<Box<StepFailure>>,
+}
+
+impl fmt::Display for StepFailure {
+    /// `Step <index> (<label>) failed: <error>`, so the cause shows however
+    /// the error is formatted.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Step {} ({}) failed: {}",
+            self.index,
+            self.step.label(),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for StepFailure {}
+
 /// Execute a workflow.
 ///
 /// This is the main entry point for running workflows.
@@ -62,63 +146,202 @@ pub struct StepResult {
 /// # Ok::<(), anyhow::Error>(())
 /// ```
 pub fn execute(workflow: &Workflow) -> Result<ExecutionResult> {
+    execute_with_context(workflow, Context::new())
+}
+
+/// Execute a workflow with a prepared context.
+///
+/// Use this to provide input variables or a secret provider:
+///
+/// ```rust,no_run
+/// use fgp_workflow::{execute_with_context, parse_yaml, Context, Value};
+/// use fgp_workflow::secrets::EnvSecretProvider;
+///
+/// let workflow = parse_yaml("name: x\nsteps:\n  - service: a\n    method: a.b\n")?;
+/// let mut ctx = Context::new().with_secrets(EnvSecretProvider::with_prefix("FGP_SECRET_"));
+/// ctx.set("account", Value::from("work"));
+///
+/// let result = execute_with_context(&workflow, ctx)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn execute_with_context(workflow: &Workflow, ctx: Context) -> Result<ExecutionResult> {
+    let redactor = ctx.redactor().clone();
+
+    run(workflow, ctx, None)
+        .map(masked)
+        .map_err(|e| redact_error(e, &redactor))
+}
+
+/// Plan a workflow without calling any daemon.
+///
+/// Transform and workflow steps are evaluated, but service steps are skipped
+/// and return `null`. Templates that depend on a skipped step's result are
+/// left as they are, so each step result shows the params the step would be
+/// called with:
+///
+/// ```rust
+/// use fgp_workflow::{dry_run, parse_yaml, Context, Value};
+///
+/// let workflow = parse_yaml(
+///     r#"
+/// name: x
+/// steps:
+///   - service: gmail
+///     method: gmail.inbox
+///     params: {limit: "{{ limit }}"}
+///     output: emails
+///   - service: browser
+///     method: browser.open
+///     params: {url: "{{ emails.0.url }}"}
+/// "#,
+/// )?;
+/// let mut ctx = Context::new();
+/// ctx.set("limit", Value::from(5));
+///
+/// let plan = dry_run(&workflow, ctx)?;
+/// assert_eq!(plan.step_results[0].params["limit"], 5);
+/// assert_eq!(plan.step_results[1].params["url"], "{{ emails.0.url }}");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn dry_run(workflow: &Workflow, ctx: Context) -> Result<ExecutionResult> {
+    let redactor = ctx.redactor().clone();
+
+    run(workflow, ctx, Some(&mut Unknown::default()))
+        .map(masked)
+        .map_err(|e| redact_error(e, &redactor))
+}
+
+/// Mask secret values in a run's result and final context.
+///
+/// Step results are masked as they are made.
+fn masked(mut result: ExecutionResult) -> ExecutionResult {
+    result.context.redact();
+    result.result = result.context.redactor().redact(&result.result);
+    result
+}
+
+/// Mask secret values in an error message.
+///
+/// Step failures are redacted when they are raised and kept as they are.
+/// Other errors are replaced by their masked message only when it holds a
+/// secret, so callers can still downcast the rest.
+fn redact_error(error: anyhow::Error, redactor: &Redactor) -> anyhow::Error {
+    if redactor.is_empty() || error.is::<StepFailure>() {
+        return error;
+    }
+
+    let message = format!("{:#}", error);
+    let masked = redactor.redact_str(&message);
+    if masked == message {
+        error
+    } else {
+        anyhow::anyhow!(masked)
+    }
+}
+
+/// Run each step of a workflow in order.
+///
+/// `unknown` is set for dry runs. The result and context are not masked,
+/// so a workflow step can pass values derived from secrets on to later
+/// steps; see [`masked`].
+fn run(
+    workflow: &Workflow,
+    mut ctx: Context,
+    mut unknown: Option<&mut Unknown>,
+) -> Result<ExecutionResult> {
     tracing::info!(workflow = %workflow.name, steps = workflow.steps.len(), "Starting workflow");
 
     let start = std::time::Instant::now();
-    let mut ctx = Context::new();
     let mut step_results = Vec::new();
 
+    // Apply input defaults and check required inputs
+    for (name, input) in &workflow.inputs {
+        if ctx.get(name).is_none() {
+            match input.default {
+                Some(ref default) => ctx.set(name, default.clone()),
+                None => anyhow::bail!("Workflow '{}' requires input '{}'", workflow.name, name),
+            }
+        }
+    }
+
     for (index, step) in workflow.steps.iter().enumerate() {
         let step_start = std::time::Instant::now();
 
-        tracing::debug!(
-            step = index,
-            service = %step.service,
-            method = %step.method,
-            "Executing step"
-        );
-
-        // Resolve parameters (expand templates)
-        let resolved_params = resolve_params(&ctx, &step.params)?;
-
-        // Call the daemon (with auto-start enabled for workflows)
-        let response = fgp_daemon::client::call_auto_start(
-            &step.service,
-            &step.method,
-            resolved_params.clone(),
-        )
-        .with_context(|| format!("Step {} ({}.{}) failed", index, step.service, step.method))?;
-
-        // Check response
-        if !response.ok {
-            let error = response.error.map(|e| e.message).unwrap_or_default();
-            anyhow::bail!(
-                "Step {} ({}.{}) returned error: {}",
-                index,
-                step.service,
-                step.method,
-                error
-            );
-        }
+        let mut resolved = None;
+        let outcome = match run_step(
+            workflow,
+            &ctx,
+            index,
+            step,
+            unknown.as_deref(),
+            &mut resolved,
+        ) {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                // Keep the steps a failed sub-workflow ran
+                let child = error
+                    .downcast_ref::<StepFailure>()
+                    .filter(|_| step.is_workflow())
+                    .map(|failure| Box::new(failure.clone()));
+                let error = redact_error(error, ctx.redactor());
+                return Err(StepFailure {
+                    index,
+                    step: step.clone(),
+                    step_results,
+                    params: resolved.map(|params| ctx.redactor().redact(&params)),
+                    error: format!("{:#}", error),
+                    child,
+                }
+                .into());
+            }
+        };
+        let StepOutcome {
+            step: executed,
+            params,
+            result,
+            children,
+            known,
+        } = outcome;
 
-        let result = response.result.unwrap_or(Value::Null);
         let step_ms = step_start.elapsed().as_secs_f64() * 1000.0;
 
         tracing::debug!(step = index, duration_ms = step_ms, "Step completed");
 
+        if let Some(ref mut unknown) = unknown {
+            unknown.record(step, known);
+        }
+
         // Store result
         ctx.push_result(result.clone());
 
-        // Store in named variable if output is specified
-        if let Some(ref output_name) = step.output {
-            ctx.set(output_name, result.clone());
+        // Store in named variables if output is specified
+        if let Some(ref output) = step.output {
+            for (name, value) in output.bind(&result) {
+                ctx.set(&name, value);
+            }
         }
 
+        // Store the step record if the step has an id
+        if let Some(ref id) = step.id {
+            ctx.set_step(
+                id,
+                serde_json::json!({
+                    "result": result,
+                    "status": "success",
+                    "duration_ms": step_ms,
+                    "params": params,
+                }),
+            );
+        }
+
+        let redactor = ctx.redactor();
         step_results.push(StepResult {
             index,
-            step: step.clone(),
-            result: result.clone(),
+            step: executed,
+            params: redactor.redact(&params),
+            result: redactor.redact(&result),
             duration_ms: step_ms,
+            children,
         });
     }
 
@@ -130,7 +353,12 @@ pub fn execute(workflow: &Workflow) -> Result<ExecutionResult> {
         "Workflow completed"
     );
 
-    let final_result = ctx.prev().cloned().unwrap_or(Value::Null);
+    let final_result = if workflow.outputs.is_empty() {
+        ctx.prev().cloned().unwrap_or(Value::Null)
+    } else {
+        resolve_params(&ctx, &workflow.outputs, unknown.as_deref())
+            .context("Failed to resolve workflow outputs")?
+    };
 
     Ok(ExecutionResult {
         result: final_result,
@@ -140,15 +368,317 @@ pub fn execute(workflow: &Workflow) -> Result<ExecutionResult> {
     })
 }
 
+/// What running a single step produced.
+struct StepOutcome {
+    /// Step with its service and method resolved
+    step: Step,
+    params: Value,
+    result: Value,
+    children: Vec<StepResult>,
+    /// Whether the result is real (false for steps skipped in a dry run)
+    known: bool,
+}
+
+/// Run a single step.
+///
+/// `resolved` is set to the step's params once they resolve, so a failure
+/// after that can report them.
+fn run_step(
+    workflow: &Workflow,
+    ctx: &Context,
+    index: usize,
+    step: &Step,
+    unknown: Option<&Unknown>,
+    resolved: &mut Option<Value>,
+) -> Result<StepOutcome> {
+    let mut executed = step.clone();
+
+    if let Some(ref transform) = step.transform {
+        tracing::debug!(step = index, "Executing transform step");
+
+        if unknown.is_some_and(|u| u.affects(transform)) {
+            return Ok(StepOutcome::skipped(
+                executed,
+                Value::Object(serde_json::Map::new()),
+            ));
+        }
+        let result = ctx.resolve_param(transform)?;
+        return Ok(StepOutcome {
+            step: executed,
+            params: Value::Object(serde_json::Map::new()),
+            result,
+            children: Vec::new(),
+            known: true,
+        });
+    }
+
+    if let Some(ref target) = step.workflow {
+        tracing::debug!(step = index, workflow = %target, "Executing workflow step");
+
+        let inputs = resolve_params(ctx, &step.params, unknown)?;
+        *resolved = Some(inputs.clone());
+        // In a dry run, inputs that depend on skipped steps are unknown to the child
+        let mut child_unknown = unknown.map(|u| {
+            Unknown(
+                step.params
+                    .iter()
+                    .filter(|(_, param)| u.affects(param))
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            )
+        });
+        let (child, known) = run_child(workflow, ctx, target, &inputs, child_unknown.as_mut())?;
+        return Ok(StepOutcome {
+            step: executed,
+            params: inputs,
+            result: child.result,
+            children: child.step_results,
+            known,
+        });
+    }
+
+    // Resolve service and method (may be templates)
+    let name = |name: &str| match unknown {
+        Some(u) if u.affects(&Param::from(Value::from(name))) => Ok(name.to_string()),
+        _ => resolve_name(ctx, name),
+    };
+    let service = name(&step.service).context("Invalid service")?;
+    let method = name(&step.method).context("Invalid method")?;
+
+    tracing::debug!(
+        step = index,
+        service = %service,
+        method = %method,
+        "Executing step"
+    );
+
+    // Resolve parameters (expand templates)
+    let params = resolve_params(ctx, &step.params, unknown)?;
+    *resolved = Some(params.clone());
+    tracing::trace!(
+        step = index,
+        params = %ctx.redactor().redact(&params),
+        "Resolved params"
+    );
+
+    executed.service = service;
+    executed.method = method;
+
+    if unknown.is_some() {
+        return Ok(StepOutcome::skipped(executed, params));
+    }
+
+    let result = call_step(&executed.service, &executed.method, params.clone())?;
+    Ok(StepOutcome {
+        step: executed,
+        params,
+        result,
+        children: Vec::new(),
+        known: true,
+    })
+}
+
+impl StepOutcome {
+    /// A step a dry run doesn't run.
+    fn skipped(step: Step, params: Value) -> Self {
+        Self {
+            step,
+            params,
+            result: Value::Null,
+            children: Vec::new(),
+            known: false,
+        }
+    }
+}
+
+/// Values a dry run can't know because they come from skipped steps.
+///
+/// Holds the roots of template references: variable names, `prev`,
+/// `results` and `steps.<id>`.
+#[derive(Debug, Default)]
+struct Unknown(HashSet<String>);
+
+impl Unknown {
+    /// Whether a param has a template that reads an unknown value.
+    fn affects(&self, param: &Param) -> bool {
+        let mut affected = false;
+        for_each_template(param, "", &mut |template, _| {
+            affected |= references(template)
+                .iter()
+                .any(|reference| self.0.contains(&root(reference)));
+        });
+        affected
+    }
+
+    /// Resolve a param, leaving templates that read unknown values as they are.
+    fn resolve(&self, ctx: &Context, param: &Param) -> Result<Value> {
+        match param {
+            Param::Template(template) if self.affects(param) => Ok(Value::String(template.clone())),
+            Param::Object(fields) => {
+                let mut resolved = serde_json::Map::new();
+                for (key, value) in fields {
+                    resolved.insert(key.clone(), self.resolve(ctx, value)?);
+                }
+                Ok(Value::Object(resolved))
+            }
+            Param::Array(items) => items
+                .iter()
+                .map(|item| self.resolve(ctx, item))
+                .collect::<Result<_>>()
+                .map(Value::Array),
+            _ => ctx.resolve_param(param),
+        }
+    }
+
+    /// Record whether the values a step sets are known.
+    fn record(&mut self, step: &Step, known: bool) {
+        let mut roots: Vec<String> = step
+            .output
+            .iter()
+            .flat_map(|o| o.names())
+            .map(String::from)
+            .collect();
+        roots.extend(["prev", "$prev"].map(String::from));
+        if let Some(ref id) = step.id {
+            roots.push(format!("steps.{}", id));
+        }
+
+        for root in roots {
+            if known {
+                self.0.remove(&root);
+            } else {
+                self.0.insert(root);
+            }
+        }
+        if !known {
+            self.0.extend(["results", "$results"].map(String::from));
+        }
+    }
+
+    /// Whether a finished workflow's result is known.
+    fn knows_result(&self, workflow: &Workflow) -> bool {
+        if workflow.outputs.is_empty() {
+            !self.0.contains("prev")
+        } else {
+            !workflow.outputs.values().any(|output| self.affects(output))
+        }
+    }
+}
+
+/// The part of a reference a dry run tracks: `steps.<id>` or the first segment.
+fn root(reference: &str) -> String {
+    let mut segments = reference.split('.');
+    match (segments.next(), segments.next()) {
+        (Some("steps"), Some(id)) => format!("steps.{}", id),
+        (root, _) => root.unwrap_or_default().to_string(),
+    }
+}
+
+/// Run the workflow of a workflow step in a child context.
+///
+/// Returns the child's result and whether it is known, which it always is
+/// outside dry runs.
+fn run_child(
+    parent: &Workflow,
+    ctx: &Context,
+    target: &str,
+    inputs: &Value,
+    unknown: Option<&mut Unknown>,
+) -> Result<(ExecutionResult, bool)> {
+    if ctx.depth() >= MAX_WORKFLOW_DEPTH {
+        anyhow::bail!(
+            "Workflow nesting exceeds the limit of {}",
+            MAX_WORKFLOW_DEPTH
+        );
+    }
+
+    let workflow = find_workflow(parent, ctx, target)?;
+    let mut child = ctx.child();
+
+    if let Value::Object(inputs) = inputs {
+        for (name, value) in inputs {
+            if !workflow.inputs.is_empty() && !workflow.inputs.contains_key(name) {
+                anyhow::bail!("Workflow '{}' has no input '{}'", workflow.name, name);
+            }
+            child.set(name, value.clone());
+        }
+    }
+
+    match unknown {
+        Some(unknown) => {
+            let result = run(&workflow, child, Some(&mut *unknown))?;
+            Ok((result, unknown.knows_result(&workflow)))
+        }
+        None => Ok((run(&workflow, child, None)?, true)),
+    }
+}
+
+/// Find the workflow a workflow step runs.
+///
+/// Registered names take precedence; otherwise the target is loaded as a
+/// file relative to the calling workflow's file.
+fn find_workflow(parent: &Workflow, ctx: &Context, target: &str) -> Result<Workflow> {
+    if let Some(workflow) = ctx.workflow(target) {
+        return Ok(workflow.clone());
+    }
+
+    if Format::from_path(target).is_none() {
+        anyhow::bail!("Workflow '{}' is not registered", target);
+    }
+
+    let path = match parent.source.as_deref().and_then(Path::parent) {
+        Some(dir) => dir.join(target),
+        None => target.into(),
+    };
+    crate::load_file(path)
+}
+
+/// Call the daemon for a service step and return its result.
+fn call_step(service: &str, method: &str, params: Value) -> Result<Value> {
+    // Call the daemon (with auto-start enabled for workflows)
+    let response = fgp_daemon::client::call_auto_start(service, method, params)?;
+
+    // Check response
+    if !response.ok {
+        let error = response.error.map(|e| e.message).unwrap_or_default();
+        anyhow::bail!("Daemon returned error: {}", error);
+    }
+
+    Ok(response.result.unwrap_or(Value::Null))
+}
+
+/// Resolve a service or method name, expanding templates.
+fn resolve_name(ctx: &Context, name: &str) -> Result<String> {
+    let resolved = if is_template(name) {
+        match ctx.resolve_param(&Param::template(name))? {
+            Value::String(s) => s,
+            other => other.to_string(),
+        }
+    } else {
+        name.to_string()
+    };
+
+    if !is_valid_name(&resolved) {
+        anyhow::bail!("'{}' rendered to invalid name '{}'", name, resolved);
+    }
+
+    Ok(resolved)
+}
+
 /// Resolve parameters, expanding templates.
 fn resolve_params(
     ctx: &Context,
-    params: &std::collections::HashMap<String, Value>,
+    params: &indexmap::IndexMap<String, Param>,
+    unknown: Option<&Unknown>,
 ) -> Result<Value> {
     let mut resolved = serde_json::Map::new();
 
-    for (key, value) in params {
-        resolved.insert(key.clone(), ctx.resolve(value)?);
+    for (key, param) in params {
+        let value = match unknown {
+            Some(unknown) => unknown.resolve(ctx, param)?,
+            None => ctx.resolve_param(param)?,
+        };
+        resolved.insert(key.clone(), value);
     }
 
     Ok(Value::Object(resolved))
@@ -161,10 +691,10 @@ mod tests {
     #[test]
     fn test_resolve_params_simple() {
         let ctx = Context::new();
-        let mut params = std::collections::HashMap::new();
-        params.insert("limit".to_string(), Value::from(10));
+        let mut params = indexmap::IndexMap::new();
+        params.insert("limit".to_string(), Param::literal(10));
 
-        let resolved = resolve_params(&ctx, &params).unwrap();
+        let resolved = resolve_params(&ctx, &params, None).unwrap();
 
         assert_eq!(resolved.get("limit"), Some(&Value::from(10)));
     }
@@ -174,17 +704,390 @@ mod tests {
         let mut ctx = Context::new();
         ctx.set("count", Value::from(5));
 
-        let mut params = std::collections::HashMap::new();
+        let mut params = indexmap::IndexMap::new();
         params.insert(
             "message".to_string(),
-            serde_json::json!({"__template__": "Found {{ count }} items"}),
+            Param::template("Found {{ count }} items"),
         );
 
-        let resolved = resolve_params(&ctx, &params).unwrap();
+        let resolved = resolve_params(&ctx, &params, None).unwrap();
 
         assert_eq!(
             resolved.get("message"),
             Some(&Value::String("Found 5 items".to_string()))
         );
     }
+
+    #[test]
+    fn test_resolve_params_order() {
+        let mut ctx = Context::new();
+        ctx.set("name", Value::from("x"));
+
+        let step = Step::call("test", "test.action")
+            .with_param("zeta", 1)
+            .with_param("alpha", "{{ name }}")
+            .with_param("nested", serde_json::json!({"b": 1, "a": "{{ name }}"}))
+            .build();
+
+        let resolved = resolve_params(&ctx, &step.params, None).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&resolved).unwrap(),
+            r#"{"zeta":1,"alpha":"x","nested":{"b":1,"a":"x"}}"#
+        );
+    }
+
+    #[test]
+    fn test_execute_transform_steps() {
+        let workflow = Workflow::new("transform")
+            .add(Step::transform(serde_json::json!({"a": 1, "b": [2, 3]})).output("data"))
+            .add(Step::transform("{{ data.b }}").output("list"))
+            .add(Step::transform(
+                serde_json::json!({"first": "{{ list.0 }}", "a": "{{ data.a }}"}),
+            ))
+            .build();
+
+        let result = execute(&workflow).unwrap();
+
+        assert_eq!(result.step_results.len(), 3);
+        assert_eq!(result.context.get("list"), Some(&serde_json::json!([2, 3])));
+        assert_eq!(result.result, serde_json::json!({"first": 2, "a": 1}));
+    }
+
+    #[test]
+    fn test_execute_step_ids() {
+        let workflow = Workflow::new("ids")
+            .add(Step::transform(serde_json::json!({"count": 2})).id("first"))
+            .add(Step::transform("{{ steps.first.result.count }}").id("second"))
+            .add(Step::transform("{{ steps.second.status }}"))
+            .build();
+
+        let result = execute(&workflow).unwrap();
+
+        assert_eq!(
+            result.context.step("second").unwrap().get("result"),
+            Some(&Value::from(2))
+        );
+        assert_eq!(result.result, Value::String("success".to_string()));
+    }
+
+    #[test]
+    fn test_execute_output_bindings() {
+        let workflow = Workflow::new("bindings")
+            .add(
+                Step::transform(serde_json::json!({
+                    "messages": [{"id": "a"}],
+                    "next_page_token": "tok"
+                }))
+                .output_path("emails", "messages")
+                .output_path("cursor", "next_page_token"),
+            )
+            .build();
+
+        let result = execute(&workflow).unwrap();
+
+        assert_eq!(
+            result.context.get("emails"),
+            Some(&serde_json::json!([{"id": "a"}]))
+        );
+        assert_eq!(result.context.get("cursor"), Some(&Value::from("tok")));
+    }
+
+    #[test]
+    fn test_execute_redacts_secrets() {
+        use crate::secrets::MemorySecretProvider;
+
+        let workflow = Workflow::new("secrets")
+            .add(
+                Step::transform(serde_json::json!({"auth": "Bearer {{ secrets.token }}"}))
+                    .output("req"),
+            )
+            .add(Step::transform("{{ secrets.missing }}"))
+            .build();
+        let ctx = Context::new().with_secrets(MemorySecretProvider::new().with("token", "s3cret"));
+
+        let err = execute_with_context(&workflow, ctx).unwrap_err();
+        assert!(err.to_string().contains("Secret 'missing' not found"));
+
+        let workflow = Workflow::new("secrets")
+            .add(
+                Step::transform(serde_json::json!({"auth": "Bearer {{ secrets.token }}"}))
+                    .output("req"),
+            )
+            .build();
+        let ctx = Context::new().with_secrets(MemorySecretProvider::new().with("token", "s3cret"));
+
+        let result = execute_with_context(&workflow, ctx).unwrap();
+        let masked = serde_json::json!({"auth": "Bearer ***"});
+        assert_eq!(result.result, masked);
+        assert_eq!(result.step_results[0].result, masked);
+        assert_eq!(result.context.get("req"), Some(&masked));
+    }
+
+    #[test]
+    fn test_child_secret_output_reaches_parent() {
+        use crate::secrets::MemorySecretProvider;
+
+        let child = Workflow::new("auth")
+            .output("token", "{{ secrets.api }}")
+            .add(Step::transform(1))
+            .build();
+        let workflow = Workflow::new("parent")
+            .add(Step::workflow("auth").output("auth"))
+            .add(Step::transform(
+                "{{#if (eq auth.token \"s3cret\")}}real{{else}}masked{{/if}}",
+            ))
+            .build();
+        let ctx = Context::new()
+            .with_secrets(MemorySecretProvider::new().with("api", "s3cret"))
+            .with_workflow("auth", child);
+
+        let result = execute_with_context(&workflow, ctx).unwrap();
+        assert_eq!(result.result, Value::from("real"));
+        assert_eq!(
+            result.step_results[0].result,
+            serde_json::json!({"token": "***"})
+        );
+        assert_eq!(
+            result.context.get("auth"),
+            Some(&serde_json::json!({"token": "***"}))
+        );
+    }
+
+    #[test]
+    fn test_redact_error_keeps_type() {
+        use crate::diagnostic::Diagnostic;
+
+        let redactor = Redactor::new();
+        redactor.add("s3cret");
+
+        let error = redact_error(Diagnostic::new("steps", "bad").into(), &redactor);
+        assert!(error.downcast_ref::<Diagnostic>().is_some());
+
+        let error = redact_error(anyhow::anyhow!("token s3cret"), &redactor);
+        assert_eq!(error.to_string(), "token ***");
+    }
+
+    #[test]
+    fn test_resolve_name() {
+        let mut ctx = Context::new();
+        ctx.set("account", Value::from("work"));
+        ctx.set("bad", Value::from("gmail work"));
+
+        assert_eq!(resolve_name(&ctx, "gmail").unwrap(), "gmail");
+        assert_eq!(
+            resolve_name(&ctx, "gmail-{{ account }}").unwrap(),
+            "gmail-work"
+        );
+        assert!(resolve_name(&ctx, "{{ missing }}").is_err());
+        assert!(resolve_name(&ctx, "{{ bad }}").is_err());
+    }
+
+    #[test]
+    fn test_execute_explicit_templates() {
+        let workflow = Workflow::new("explicit")
+            .templates(crate::TemplateMode::Explicit)
+            .add(Step::transform(Value::from("x")).output("name"))
+            .add(Step::transform(serde_json::json!({
+                "literal": "{{ name }}",
+                "rendered": {"__template__": "{{ name }}"}
+            })))
+            .build();
+
+        let result = execute(&workflow).unwrap();
+
+        assert_eq!(
+            result.result,
+            serde_json::json!({"literal": "{{ name }}", "rendered": "x"})
+        );
+    }
+
+    #[test]
+    fn test_execute_sub_workflow() {
+        let child = Workflow::new("double")
+            .input("n")
+            .output("doubled", serde_json::json!(["{{ n }}", "{{ n }}"]))
+            .add(Step::transform("{{ n }}").output("seen"))
+            .build();
+        let parent = Workflow::new("parent")
+            .add(Step::transform(3).output("x"))
+            .add(
+                Step::workflow("double")
+                    .with_param("n", "{{ x }}")
+                    .output("pair"),
+            )
+            .build();
+        let ctx = Context::new().with_workflow("double", child);
+
+        let result = execute_with_context(&parent, ctx).unwrap();
+
+        assert_eq!(result.result, serde_json::json!({"doubled": [3, 3]}));
+        assert_eq!(result.step_results[1].children.len(), 1);
+        assert_eq!(result.step_results[1].children[0].result, Value::from(3));
+        // The child's variables don't leak into the parent
+        assert_eq!(result.context.get("seen"), None);
+    }
+
+    #[test]
+    fn test_sub_workflow_inputs() {
+        let child = Workflow::new("greet")
+            .input("name")
+            .input_default("greeting", "Hello")
+            .add(Step::transform("{{ greeting }}, {{ name }}"))
+            .build();
+        let ctx = || Context::new().with_workflow("greet", child.clone());
+
+        let call = |step: crate::StepBuilder| Workflow::new("parent").add(step).build();
+
+        let result = execute_with_context(
+            &call(Step::workflow("greet").with_param("name", "Ann")),
+            ctx(),
+        )
+        .unwrap();
+        assert_eq!(result.result, Value::from("Hello, Ann"));
+
+        let err = execute_with_context(&call(Step::workflow("greet")), ctx()).unwrap_err();
+        assert!(format!("{:#}", err).contains("requires input 'name'"));
+
+        let err = execute_with_context(
+            &call(
+                Step::workflow("greet")
+                    .with_param("name", "Ann")
+                    .with_param("x", 1),
+            ),
+            ctx(),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("has no input 'x'"));
+    }
+
+    #[test]
+    fn test_sub_workflow_depth_limit() {
+        let recursive = Workflow::new("loop").add(Step::workflow("loop")).build();
+        let ctx = Context::new().with_workflow("loop", recursive.clone());
+
+        let err = execute_with_context(&recursive, ctx).unwrap_err();
+        assert!(format!("{:#}", err).contains("exceeds the limit of 16"));
+    }
+
+    #[test]
+    fn test_sub_workflow_from_file() {
+        let dir = crate::testing::TempDir::new("sub-workflow");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/child.yaml"),
+            "name: child
+steps:
+  - set: {ok: true}
+",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main.yaml"),
+            "name: main
+steps:
+  - workflow: lib/child.yaml
+",
+        )
+        .unwrap();
+
+        let workflow = crate::load_file(dir.join("main.yaml")).unwrap();
+        let result = execute(&workflow).unwrap();
+
+        assert_eq!(result.result, serde_json::json!({"ok": true}));
+    }
+
+    #[test]
+    fn test_step_failure() {
+        let workflow = Workflow::new("fails")
+            .add(Step::transform(1).output("x"))
+            .add(Step::transform("{{ secrets.missing }}"))
+            .build();
+
+        let err = execute(&workflow).unwrap_err();
+        let failure = err.downcast_ref::<StepFailure>().unwrap();
+
+        assert_eq!(failure.index, 1);
+        assert!(failure.step.is_transform());
+        assert_eq!(failure.step_results.len(), 1);
+        assert_eq!(failure.step_results[0].result, Value::from(1));
+        assert_eq!(
+            err.to_string(),
+            "Step 1 (transform) failed: Secret 'missing' not found"
+        );
+        assert_eq!(format!("{:#}", err), err.to_string());
+    }
+
+    #[test]
+    fn test_sub_workflow_failure_keeps_child_steps() {
+        let child = Workflow::new("child")
+            .add(Step::transform(2).output("n"))
+            .add(Step::transform("{{ secrets.missing }}"))
+            .build();
+        let workflow = Workflow::new("parent")
+            .add(Step::transform(1))
+            .add(Step::workflow("child"))
+            .build();
+
+        let ctx = Context::new().with_workflow("child", child);
+        let err = execute_with_context(&workflow, ctx).unwrap_err();
+        let failure = err.downcast_ref::<StepFailure>().unwrap();
+
+        assert_eq!(failure.index, 1);
+        assert_eq!(failure.step_results.len(), 1);
+        let child = failure.child.as_ref().unwrap();
+        assert_eq!(child.index, 1);
+        assert!(child.step.is_transform());
+        assert_eq!(child.step_results[0].result, Value::from(2));
+        assert!(child.child.is_none());
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let child = Workflow::new("child")
+            .input("query")
+            .output("found", "{{ hits }}")
+            .add(
+                Step::call("search", "search.query")
+                    .with_param("q", "{{ query }}")
+                    .output("hits"),
+            )
+            .build();
+        let workflow = Workflow::new("plan")
+            .add(Step::transform(serde_json::json!({"limit": 5})).output("opts"))
+            .add(
+                Step::call("gmail", "gmail.inbox")
+                    .id("inbox")
+                    .with_param("limit", "{{ opts.limit }}")
+                    .output("emails"),
+            )
+            .add(Step::transform("{{ emails.0 }}").output("first"))
+            .add(
+                Step::call("browser", "browser.open")
+                    .with_param("url", "{{ first.url }}")
+                    .with_param("tab", "{{ steps.inbox.result.tab }}")
+                    .with_param("new", true),
+            )
+            .add(Step::workflow("child").with_param("query", "{{ opts.limit }}"))
+            .add(Step::transform("{{ prev.found }}"))
+            .build();
+        let ctx = Context::new().with_workflow("child", child);
+
+        let plan = dry_run(&workflow, ctx).unwrap();
+        let steps = &plan.step_results;
+
+        assert_eq!(steps[0].result, serde_json::json!({"limit": 5}));
+        assert_eq!(steps[1].params, serde_json::json!({"limit": 5}));
+        assert_eq!(steps[1].result, Value::Null);
+        assert_eq!(
+            steps[3].params,
+            serde_json::json!({
+                "url": "{{ first.url }}",
+                "tab": "{{ steps.inbox.result.tab }}",
+                "new": true
+            })
+        );
+        assert_eq!(steps[4].children[0].params, serde_json::json!({"q": 5}));
+        assert_eq!(steps[5].result, Value::Null);
+    }
 }
@@ -0,0 +1,459 @@
+//! Workflow definition formats.
+//!
+//! Workflows can be written in YAML, JSON or TOML. Every format is read into
+//! the same document model, so template handling and validation are shared
+//! and a workflow loads identically from all three.
+
+use crate::diagnostic::{in_file, Diagnostic, Location, Locator, Origin, SOURCE_KEY};
+use crate::param::{RAW_KEY, TEMPLATE_KEY};
+use crate::step::{is_template, Step};
+use crate::validate::check_structure;
+use crate::{TemplateMode, Workflow};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A workflow definition format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// YAML (`.yaml`, `.yml`)
+    Yaml,
+
+    /// JSON (`.json`)
+    Json,
+
+    /// TOML (`.toml`)
+    Toml,
+}
+
+impl Format {
+    /// Pick the format from a file extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl Format {
+    /// Pick the format from a file extension, failing for unknown ones.
+    pub(crate) fn from_path_or_err(path: &Path) -> Result<Self> {
+        Format::from_path(path).with_context(|| {
+            format!(
+                "Unknown workflow file format (expected .yaml, .yml, .json or .toml): {}",
+                path.display()
+            )
+        })
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Yaml => write!(f, "YAML"),
+            Format::Json => write!(f, "JSON"),
+            Format::Toml => write!(f, "TOML"),
+        }
+    }
+}
+
+/// Parse a workflow from a string in the given format.
+///
+/// # Example
+///
+/// ```rust
+/// use fgp_workflow::{from_str_with_format, Format};
+///
+/// let json = r#"{"name": "inbox", "steps": [{"service": "gmail", "method": "gmail.inbox"}]}"#;
+///
+/// let workflow = from_str_with_format(json, Format::Json)?;
+/// assert_eq!(workflow.name, "inbox");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn from_str_with_format(content: &str, format: Format) -> Result<Workflow> {
+    match format {
+        Format::Yaml => crate::yaml::parse_yaml(content),
+        Format::Json => crate::json::parse_json(content),
+        Format::Toml => crate::toml::parse_toml(content),
+    }
+}
+
+/// Load a workflow file, picking the format from its extension.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// let workflow = fgp_workflow::load_file("workflows/digest.toml")?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn load_file(path: impl AsRef<Path>) -> Result<Workflow> {
+    let path = path.as_ref();
+    load_file_as(path, Format::from_path_or_err(path)?)
+}
+
+/// Load a workflow file in the given format, resolving its includes.
+pub(crate) fn load_file_as(path: &Path, format: Format) -> Result<Workflow> {
+    load_file_tracking(path, format, &mut Vec::new())
+}
+
+/// Load a workflow file like [`load_file_as`], adding each file it reads
+/// (the workflow file and its includes) to `files`.
+pub(crate) fn load_file_tracking(
+    path: &Path,
+    format: Format,
+    files: &mut Vec<PathBuf>,
+) -> Result<Workflow> {
+    let doc = crate::include::load(path, format, files)?;
+
+    let mut workflow =
+        from_document(doc, format, Locator::file(path)).map_err(|e| in_file(e, path))?;
+    workflow.source = Some(path.to_path_buf());
+
+    Ok(workflow)
+}
+
+/// Parse a workflow from a string in the given format, reporting errors
+/// with their position in the string.
+pub(crate) fn parse_str(content: &str, format: Format) -> Result<Workflow> {
+    let doc = parse_document(content, format)?;
+    from_document(doc, format, Locator::text(content, format))
+}
+
+/// Parse a string into a document, without resolving includes.
+///
+/// Syntax errors are reported as [`Diagnostic`]s pointing into the string.
+pub(crate) fn parse_document(content: &str, format: Format) -> Result<serde_yaml::Value> {
+    let syntax = |message: String, line: usize, column: usize| -> anyhow::Error {
+        let suffix = format!(" at line {} column {}", line, column);
+        let message = message.strip_suffix(&suffix).unwrap_or(&message);
+        let message = format!("Failed to parse workflow {}: {}", format, message);
+        Diagnostic::syntax(content, message, Location { line, column }).into()
+    };
+
+    match format {
+        Format::Yaml => {
+            let value = serde_yaml::from_str(content).map_err(|e| match e.location() {
+                Some(at) => syntax(e.to_string(), at.line(), at.column()),
+                None => {
+                    anyhow::Error::new(e).context(format!("Failed to parse workflow {}", format))
+                }
+            })?;
+            crate::yaml::expand_tags(value)
+        }
+        Format::Json => {
+            serde_json::from_str(content).map_err(|e| syntax(e.to_string(), e.line(), e.column()))
+        }
+        Format::Toml => ::toml::from_str(content).map_err(|e| {
+            let start = e.span().map_or(0, |span| span.start);
+            let before = &content[..start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+            syntax(e.message().to_string(), line, column)
+        }),
+    }
+}
+
+/// Build and validate a workflow from a parsed document.
+///
+/// Problems are reported as [`Diagnostic`]s located with `locator`.
+pub(crate) fn from_document(
+    mut doc: serde_yaml::Value,
+    format: Format,
+    mut locator: Locator,
+) -> Result<Workflow> {
+    locator.origins = take_origins(&mut doc);
+
+    let mut workflow = build(doc, format).map_err(|d| locator.locate(*d))?;
+    workflow.origins = locator.origins;
+
+    Ok(workflow)
+}
+
+/// Remove the origins recorded on steps by the include resolver.
+fn take_origins(doc: &mut serde_yaml::Value) -> Vec<Option<Origin>> {
+    let Some(steps) = doc.get_mut("steps").and_then(|s| s.as_sequence_mut()) else {
+        return Vec::new();
+    };
+
+    steps
+        .iter_mut()
+        .map(|step| {
+            let origin = step.as_mapping_mut()?.remove(SOURCE_KEY)?;
+            Origin::from_value(&origin)
+        })
+        .collect()
+}
+
+/// Deserialize and validate a workflow document.
+fn build(
+    mut doc: serde_yaml::Value,
+    format: Format,
+) -> std::result::Result<Workflow, Box<Diagnostic>> {
+    if let Some(steps) = doc.get("steps").and_then(|s| s.as_sequence()) {
+        for (i, step) in steps.iter().enumerate() {
+            if let Some(target) = step.get("include") {
+                return Err(Diagnostic::new(
+                    format!("steps.{}.include", i),
+                    format!(
+                        "Step {} includes {:?}, but includes are only resolved when loading a file",
+                        i,
+                        target.as_str().unwrap_or_default()
+                    ),
+                )
+                .into());
+            }
+        }
+    }
+
+    let mode = template_mode(&doc).map_err(|e| Diagnostic::new("templates", format!("{:#}", e)))?;
+    if mode == TemplateMode::Explicit {
+        for_each_param_tree(&mut doc, |v| mark_bare_templates(v, RAW_KEY));
+    }
+
+    let workflow = Workflow::deserialize(&doc).map_err(|e| {
+        // Point at the first step that doesn't parse on its own
+        let steps = doc.get("steps").and_then(|s| s.as_sequence());
+        for (i, step) in steps.into_iter().flatten().enumerate() {
+            if let Err(e) = Step::deserialize(step) {
+                return Diagnostic::new(
+                    format!("steps.{}", i),
+                    format!("Step {} is invalid: {}", i, e),
+                );
+            }
+        }
+        Diagnostic::new("", format!("Failed to parse workflow {}: {}", format, e))
+    })?;
+
+    if let Some(diagnostic) = check_structure(&workflow).into_iter().next() {
+        return Err(diagnostic.into());
+    }
+
+    Ok(workflow)
+}
+
+/// Serialize a workflow to a document that reads back identically.
+///
+/// With `tags`, markers are written as YAML `!raw`/`!template` tags;
+/// otherwise they stay `__raw__`/`__template__` objects (for JSON and TOML).
+pub(crate) fn to_document(workflow: &Workflow, tags: bool) -> Result<serde_yaml::Value> {
+    let mut doc = serde_yaml::to_value(workflow).context("Failed to serialize workflow")?;
+
+    if workflow.templates == TemplateMode::Explicit {
+        for_each_param_tree(&mut doc, |v| mark_bare_templates(v, TEMPLATE_KEY));
+    }
+    if tags {
+        for_each_param_tree(&mut doc, collapse_markers);
+    }
+
+    Ok(doc)
+}
+
+/// Read the `templates` mode of a workflow document.
+fn template_mode(doc: &serde_yaml::Value) -> Result<TemplateMode> {
+    match doc.get("templates") {
+        Some(mode) => serde_yaml::from_value(mode.clone()).context("Invalid templates mode"),
+        None => Ok(TemplateMode::Auto),
+    }
+}
+
+/// Mark every template in a document explicitly, according to its own mode.
+///
+/// Used for included files, whose steps end up in a workflow that may use a
+/// different template mode.
+pub(crate) fn mark_templates_explicitly(doc: &mut serde_yaml::Value) -> Result<()> {
+    let key = match template_mode(doc)? {
+        TemplateMode::Auto => TEMPLATE_KEY,
+        TemplateMode::Explicit => RAW_KEY,
+    };
+    for_each_param_tree(doc, |v| mark_bare_templates(v, key));
+    Ok(())
+}
+
+/// Apply a function to the workflow outputs and to the params and transform
+/// of every step in a document, including steps in `fragments`.
+fn for_each_param_tree(doc: &mut serde_yaml::Value, mut f: impl FnMut(&mut serde_yaml::Value)) {
+    let mut step_lists: Vec<&mut serde_yaml::Value> = Vec::new();
+    let serde_yaml::Value::Mapping(map) = doc else {
+        return;
+    };
+    for (key, value) in map.iter_mut() {
+        match key.as_str() {
+            Some("steps") => step_lists.push(value),
+            Some("outputs") => {
+                if let Some(outputs) = value.as_mapping_mut() {
+                    outputs.values_mut().for_each(&mut f);
+                }
+            }
+            Some("fragments") => {
+                if let Some(fragments) = value.as_mapping_mut() {
+                    step_lists.extend(fragments.values_mut());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for steps in step_lists {
+        let Some(steps) = steps.as_sequence_mut() else {
+            continue;
+        };
+        for step in steps {
+            for key in ["params", "transform", "set"] {
+                if let Some(value) = step.get_mut(key) {
+                    f(value);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a mapping is a `__template__` or `__raw__` marker.
+fn is_marker(map: &serde_yaml::Mapping) -> bool {
+    map.len() == 1 && (map.contains_key(TEMPLATE_KEY) || map.contains_key(RAW_KEY))
+}
+
+/// Wrap bare template strings in a marker object.
+///
+/// With [`TemplateMode::Explicit`], bare strings are marked `__raw__` when
+/// reading and templates are marked `__template__` when writing.
+fn mark_bare_templates(value: &mut serde_yaml::Value, key: &str) {
+    use serde_yaml::{Mapping, Value};
+
+    match value {
+        Value::String(s) if is_template(s) => {
+            let mut map = Mapping::new();
+            map.insert(Value::String(key.to_string()), value.clone());
+            *value = Value::Mapping(map);
+        }
+        Value::Mapping(map) if !is_marker(map) => {
+            map.values_mut().for_each(|v| mark_bare_templates(v, key));
+        }
+        Value::Sequence(seq) => seq.iter_mut().for_each(|v| mark_bare_templates(v, key)),
+        _ => {}
+    }
+}
+
+/// Replace marker objects with `!raw` and `!template` tags.
+fn collapse_markers(value: &mut serde_yaml::Value) {
+    use serde_yaml::value::{Tag, TaggedValue};
+    use serde_yaml::Value;
+
+    match value {
+        Value::Mapping(map) if is_marker(map) => {
+            let (key, inner) = map.iter().next().expect("marker has one entry");
+            let tag = if key.as_str() == Some(RAW_KEY) {
+                "raw"
+            } else {
+                "template"
+            };
+            *value = Value::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(tag),
+                value: inner.clone(),
+            }));
+        }
+        Value::Mapping(map) => map.values_mut().for_each(collapse_markers),
+        Value::Sequence(seq) => seq.iter_mut().for_each(collapse_markers),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path("a/b.yaml"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("b.YML"), Some(Format::Yaml));
+        assert_eq!(Format::from_path("b.json"), Some(Format::Json));
+        assert_eq!(Format::from_path("b.toml"), Some(Format::Toml));
+        assert_eq!(Format::from_path("b.txt"), None);
+        assert_eq!(Format::from_path("b"), None);
+    }
+
+    #[test]
+    fn test_same_workflow_from_all_formats() {
+        let yaml = r#"
+name: inbox
+steps:
+  - id: fetch
+    service: gmail
+    method: gmail.inbox
+    params:
+      limit: 5
+      query: "from:{{ sender }}"
+      literal: !raw "{{ x }}"
+    output: emails
+"#;
+        let json = r#"{
+  "name": "inbox",
+  "steps": [
+    {
+      "id": "fetch",
+      "service": "gmail",
+      "method": "gmail.inbox",
+      "params": {
+        "limit": 5,
+        "query": "from:{{ sender }}",
+        "literal": {"__raw__": "{{ x }}"}
+      },
+      "output": "emails"
+    }
+  ]
+}"#;
+        let toml = r#"
+name = "inbox"
+
+[[steps]]
+id = "fetch"
+service = "gmail"
+method = "gmail.inbox"
+output = "emails"
+
+[steps.params]
+limit = 5
+query = "from:{{ sender }}"
+literal = { __raw__ = "{{ x }}" }
+"#;
+
+        let from_yaml = from_str_with_format(yaml, Format::Yaml).unwrap();
+        let from_json = from_str_with_format(json, Format::Json).unwrap();
+        let from_toml = from_str_with_format(toml, Format::Toml).unwrap();
+
+        assert_eq!(from_yaml, from_json);
+        assert_eq!(from_yaml, from_toml);
+    }
+
+    #[test]
+    fn test_shared_validation() {
+        let json = r#"{"name": "", "steps": [{"service": "a", "method": "a.b"}]}"#;
+        let toml = "name = \"x\"\nsteps = []\n";
+
+        let err = from_str_with_format(json, Format::Json).unwrap_err();
+        assert!(err.to_string().contains("name cannot be empty"));
+
+        let err = from_str_with_format(toml, Format::Toml).unwrap_err();
+        assert!(err.to_string().contains("at least one step"));
+    }
+
+    #[test]
+    fn test_load_file_by_extension() {
+        let workflow = Workflow::new("by-ext")
+            .add(crate::Step::call("gmail", "gmail.inbox").with_param("q", "{{ q }}"))
+            .build();
+        let dir = crate::testing::TempDir::new("format");
+
+        crate::yaml::save_file(&workflow, dir.join("w.yaml")).unwrap();
+        crate::json::save_file(&workflow, dir.join("w.json")).unwrap();
+        crate::toml::save_file(&workflow, dir.join("w.toml")).unwrap();
+
+        for name in ["w.yaml", "w.json", "w.toml"] {
+            assert_eq!(load_file(dir.join(name)).unwrap(), workflow, "{}", name);
+        }
+        assert!(load_file(dir.join("w.txt")).is_err());
+    }
+}
@@ -0,0 +1,501 @@
+//! Step graph of a workflow.
+//!
+//! Steps run in order, and a step also depends on the earlier steps whose
+//! results its templates read, through an output variable, `prev` or
+//! `steps.<id>`:
+//!
+//! ```rust
+//! use fgp_workflow::graph::Graph;
+//! use fgp_workflow::{Step, Workflow};
+//!
+//! let workflow = Workflow::new("open-first")
+//!     .add(Step::call("gmail", "gmail.inbox").output("emails"))
+//!     .add(Step::call("browser", "browser.open").with_param("url", "{{ emails.0.url }}"))
+//!     .build();
+//!
+//! let graph = Graph::from_workflow(&workflow);
+//! assert_eq!(graph.dependencies(1).count(), 1);
+//! ```
+//!
+//! Besides the text listing of its `Display`, a graph exports to Graphviz
+//! DOT ([`Graph::to_dot`]) and Mermaid ([`Graph::to_mermaid`]) for design
+//! docs. Data edges are drawn solid, labeled with the variables they carry,
+//! and ordering edges dashed where no data edge already joins the steps:
+//!
+//! ```text
+//! flowchart TD
+//!   s0["gmail.inbox"]
+//!   s1["browser.open"]
+//!   s2(["transform"])
+//!   s0 -->|"emails"| s1
+//!   s1 -.-> s2
+//! ```
+
+use crate::validate::{for_each_step_template, references};
+use crate::{Step, Workflow};
+use serde_json::Value;
+use std::fmt;
+
+/// Steps of a workflow and the edges between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph {
+    /// Workflow name
+    pub name: String,
+
+    /// One node per step, in order
+    pub nodes: Vec<Node>,
+
+    /// Ordering and data edges
+    pub edges: Vec<Edge>,
+}
+
+/// A step in a [`Graph`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    /// Step index (0-based)
+    pub index: usize,
+
+    /// What the step runs: `service.method`, `transform` or
+    /// `workflow <target>`. A method already named after its service
+    /// (`gmail.inbox` on `gmail`) is not prefixed again.
+    pub label: String,
+
+    /// Kind of step
+    pub kind: NodeKind,
+
+    /// Step description
+    pub description: Option<String>,
+}
+
+/// Kind of [`Node`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// Calls a service method.
+    Service,
+
+    /// Computes a value without calling a service.
+    Transform,
+
+    /// Runs another workflow.
+    Workflow,
+}
+
+/// An edge from an earlier step to a later one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    /// Index of the earlier step
+    pub from: usize,
+
+    /// Index of the later step
+    pub to: usize,
+
+    /// Why the later step depends on the earlier one
+    pub kind: EdgeKind,
+}
+
+/// Kind of [`Edge`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKind {
+    /// The later step runs next.
+    Next,
+
+    /// The later step reads the earlier one's result through these
+    /// variables (`emails`, `prev`, `steps.inbox`).
+    Data(Vec<String>),
+}
+
+/// What a step runs, with the service of a service call.
+fn node_label(step: &Step) -> String {
+    if step.is_transform() || step.workflow.is_some() {
+        return step.label();
+    }
+    match step.method.strip_prefix(step.service.as_str()) {
+        Some(rest) if rest.starts_with('.') => step.method.clone(),
+        _ => format!("{}.{}", step.service, step.method),
+    }
+}
+
+impl Graph {
+    /// Build the graph of a workflow's steps.
+    ///
+    /// Data edges come from template references; a variable is attributed to
+    /// the last step before the reader that sets it, and references to
+    /// inputs or unknown names add no edge.
+    pub fn from_workflow(workflow: &Workflow) -> Self {
+        let nodes = workflow
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| Node {
+                index,
+                label: node_label(step),
+                kind: if step.is_transform() {
+                    NodeKind::Transform
+                } else if step.workflow.is_some() {
+                    NodeKind::Workflow
+                } else {
+                    NodeKind::Service
+                },
+                description: step.description.clone(),
+            })
+            .collect();
+
+        let mut edges = Vec::new();
+        for (to, step) in workflow.steps.iter().enumerate() {
+            if to > 0 {
+                edges.push(Edge {
+                    from: to - 1,
+                    to,
+                    kind: EdgeKind::Next,
+                });
+            }
+
+            // Variables read from each earlier step, in order of first use
+            let mut reads: Vec<(usize, Vec<String>)> = Vec::new();
+            for_each_step_template(step, to, &mut |template, _| {
+                for reference in references(template) {
+                    let Some((from, variable)) = source(workflow, to, &reference) else {
+                        continue;
+                    };
+                    let position = match reads.iter().position(|(f, _)| *f == from) {
+                        Some(position) => position,
+                        None => {
+                            reads.push((from, Vec::new()));
+                            reads.len() - 1
+                        }
+                    };
+                    if !reads[position].1.contains(&variable) {
+                        reads[position].1.push(variable);
+                    }
+                }
+            });
+
+            reads.sort_by_key(|(from, _)| *from);
+            edges.extend(reads.into_iter().map(|(from, variables)| Edge {
+                from,
+                to,
+                kind: EdgeKind::Data(variables),
+            }));
+        }
+
+        Graph {
+            name: workflow.name.clone(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// Data edges into a step.
+    pub fn dependencies(&self, index: usize) -> impl Iterator<Item = &Edge> {
+        self.edges
+            .iter()
+            .filter(move |edge| edge.to == index && matches!(edge.kind, EdgeKind::Data(_)))
+    }
+
+    /// Graphviz DOT source of the graph.
+    ///
+    /// Service steps are boxes, transforms ellipses and sub-workflows
+    /// components; each is labeled with what it runs and its description.
+    pub fn to_dot(&self) -> String {
+        let mut out = format!("digraph {} {{\n", dot_string(&self.name));
+        out.push_str("  node [shape=box];\n");
+
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::Service => "",
+                NodeKind::Transform => ", shape=ellipse",
+                NodeKind::Workflow => ", shape=component",
+            };
+            out.push_str(&format!(
+                "  s{} [label={}{}];\n",
+                node.index,
+                dot_string(&node.text()),
+                shape
+            ));
+        }
+
+        for edge in self.drawn_edges() {
+            match edge.kind {
+                EdgeKind::Next => out.push_str(&format!(
+                    "  s{} -> s{} [style=dashed, color=gray];\n",
+                    edge.from, edge.to
+                )),
+                EdgeKind::Data(ref variables) => out.push_str(&format!(
+                    "  s{} -> s{} [label={}];\n",
+                    edge.from,
+                    edge.to,
+                    dot_string(&variables.join(", "))
+                )),
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid flowchart source of the graph.
+    ///
+    /// Service steps are rectangles, transforms stadiums and sub-workflows
+    /// subroutines; each is labeled with what it runs and its description.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = format!(
+            "---\ntitle: {}\n---\nflowchart TD\n",
+            Value::from(self.name.as_str())
+        );
+
+        for node in &self.nodes {
+            let label = mermaid_string(&node.text());
+            let (open, close) = match node.kind {
+                NodeKind::Service => ("[", "]"),
+                NodeKind::Transform => ("([", "])"),
+                NodeKind::Workflow => ("[[", "]]"),
+            };
+            out.push_str(&format!("  s{}{}{}{}\n", node.index, open, label, close));
+        }
+
+        for edge in self.drawn_edges() {
+            match edge.kind {
+                EdgeKind::Next => out.push_str(&format!("  s{} -.-> s{}\n", edge.from, edge.to)),
+                EdgeKind::Data(ref variables) => out.push_str(&format!(
+                    "  s{} -->|{}| s{}\n",
+                    edge.from,
+                    mermaid_string(&variables.join(", ")),
+                    edge.to
+                )),
+            }
+        }
+
+        out
+    }
+
+    /// Edges to draw: ordering edges are left out where a data edge joins
+    /// the same steps.
+    fn drawn_edges(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(|edge| {
+            edge.kind != EdgeKind::Next
+                || !self
+                    .dependencies(edge.to)
+                    .any(|data| data.from == edge.from)
+        })
+    }
+}
+
+impl Node {
+    /// The label, followed by the description on its own line.
+    fn text(&self) -> String {
+        match self.description {
+            Some(ref description) => format!("{}\n{}", self.label, description.trim_end()),
+            None => self.label.clone(),
+        }
+    }
+}
+
+/// A quoted DOT string.
+fn dot_string(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// A quoted Mermaid string; quotes become entity codes and newlines breaks.
+fn mermaid_string(s: &str) -> String {
+    let escaped = s.replace('"', "#quot;").replace('\n', "<br/>");
+    format!("\"{}\"", escaped)
+}
+
+/// Text listing of the steps, each followed by the data it reads:
+///
+/// ```text
+/// open-first
+///   [0] gmail.inbox
+///   [1] browser.open
+///       <- [0] emails
+/// ```
+impl fmt::Display for Graph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+
+        for node in &self.nodes {
+            write!(f, "  [{}] {}", node.index, node.label)?;
+            match node.description {
+                Some(ref description) => writeln!(f, ": {}", description)?,
+                None => writeln!(f)?,
+            }
+
+            for edge in self.dependencies(node.index) {
+                if let EdgeKind::Data(ref variables) = edge.kind {
+                    writeln!(f, "      <- [{}] {}", edge.from, variables.join(", "))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The step a reference made by step `to` reads, and the variable it reads
+/// it through.
+fn source(workflow: &Workflow, to: usize, reference: &str) -> Option<(usize, String)> {
+    let mut segments = reference.split('.');
+    let root = segments.next()?;
+    let mut earlier = workflow.steps[..to].iter().enumerate().rev();
+
+    match root {
+        "prev" | "$prev" => to.checked_sub(1).map(|from| (from, "prev".to_string())),
+        "steps" => {
+            let id = segments.next()?;
+            earlier
+                .find(|(_, step)| step.id.as_deref() == Some(id))
+                .map(|(from, _)| (from, format!("steps.{}", id)))
+        }
+        _ => earlier
+            .find(|(_, step)| {
+                step.output
+                    .as_ref()
+                    .is_some_and(|output| output.names().contains(&root))
+            })
+            .map(|(from, _)| (from, root.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_yaml;
+
+    #[test]
+    fn test_graph_edges() {
+        let workflow = parse_yaml(
+            r#"
+name: digest
+inputs:
+  account:
+steps:
+  - id: inbox
+    service: "gmail-{{ account }}"
+    method: gmail.inbox
+    output: emails
+  - set: "{{ emails.0 }}"
+    output: first
+    description: Pick the first email
+  - service: browser
+    method: browser.open
+    params:
+      url: "{{ first.url }}"
+      tab: "{{ steps.inbox.result.tab }}"
+      title: "{{ prev.subject }} ({{ first.from }})"
+"#,
+        )
+        .unwrap();
+
+        let graph = Graph::from_workflow(&workflow);
+
+        assert_eq!(graph.nodes[0].label, "gmail-{{ account }}.gmail.inbox");
+        assert_eq!(graph.nodes[1].label, "transform");
+        assert_eq!(graph.dependencies(0).count(), 0);
+
+        let into_last: Vec<_> = graph.dependencies(2).collect();
+        assert_eq!(
+            into_last,
+            vec![
+                &Edge {
+                    from: 0,
+                    to: 2,
+                    kind: EdgeKind::Data(vec!["steps.inbox".to_string()]),
+                },
+                &Edge {
+                    from: 1,
+                    to: 2,
+                    kind: EdgeKind::Data(vec!["first".to_string(), "prev".to_string()]),
+                },
+            ]
+        );
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .filter(|e| e.kind == EdgeKind::Next)
+                .count(),
+            2
+        );
+
+        assert_eq!(
+            graph.to_string(),
+            "digest
+  [0] gmail-{{ account }}.gmail.inbox
+  [1] transform: Pick the first email
+      <- [0] emails
+  [2] browser.open
+      <- [0] steps.inbox
+      <- [1] first, prev
+"
+        );
+    }
+
+    #[test]
+    fn test_graph_uses_last_setter() {
+        let workflow = Workflow::new("overwrite")
+            .add(Step::transform(1).output("x"))
+            .add(Step::transform(2).output("x"))
+            .add(Step::transform("{{ x }}"))
+            .build();
+
+        let graph = Graph::from_workflow(&workflow);
+        let sources: Vec<usize> = graph.dependencies(2).map(|e| e.from).collect();
+
+        assert_eq!(sources, vec![1]);
+    }
+
+    fn exported() -> Graph {
+        let workflow = Workflow::new("open \"first\"")
+            .add(Step::call("gmail", "gmail.inbox").output("emails"))
+            .add(
+                Step::transform("{{ emails.0 }}")
+                    .output("first")
+                    .description("Pick the \"first\" one"),
+            )
+            .add(Step::workflow("notify").with_param("email", "{{ first }}"))
+            .add(Step::call("chat", "slack.post"))
+            .build();
+        Graph::from_workflow(&workflow)
+    }
+
+    #[test]
+    fn test_to_dot() {
+        assert_eq!(
+            exported().to_dot(),
+            r#"digraph "open \"first\"" {
+  node [shape=box];
+  s0 [label="gmail.inbox"];
+  s1 [label="transform\nPick the \"first\" one", shape=ellipse];
+  s2 [label="workflow notify", shape=component];
+  s3 [label="chat.slack.post"];
+  s0 -> s1 [label="emails"];
+  s1 -> s2 [label="first"];
+  s2 -> s3 [style=dashed, color=gray];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        assert_eq!(
+            exported().to_mermaid(),
+            r#"---
+title: "open \"first\""
+---
+flowchart TD
+  s0["gmail.inbox"]
+  s1(["transform<br/>Pick the #quot;first#quot; one"])
+  s2[["workflow notify"]]
+  s3["chat.slack.post"]
+  s0 -->|"emails"| s1
+  s1 -->|"first"| s2
+  s2 -.-> s3
+"#
+        );
+    }
+}
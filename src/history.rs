@@ -0,0 +1,754 @@
+//! Persistent history of workflow runs.
+//!
+//! A [`History`] appends a [`RunRecord`] per run to a JSON Lines file
+//! (`~/.fgp/history/runs.jsonl` by default) and answers questions like "when
+//! did the digest workflow last fail, and why?":
+//!
+//! ```rust,no_run
+//! use fgp_workflow::history::{History, RunFilter, RunStatus};
+//!
+//! let history = History::open(History::default_path().unwrap());
+//! let failures = history.list(
+//!     &RunFilter::new()
+//!         .workflow("digest")
+//!         .status(RunStatus::Failed)
+//!         .limit(1),
+//! )?;
+//! if let Some(run) = failures.first() {
+//!     println!("{}", run);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Step params and results larger than [`MAX_VALUE_BYTES`] (as JSON) are
+//! replaced by a short preview, so large inboxes don't bloat the file.
+
+use crate::{ExecutionResult, StepFailure, StepResult, Workflow};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Default limit on the JSON size of a recorded param or result.
+pub const MAX_VALUE_BYTES: usize = 4096;
+
+/// How long a writer waits for another to finish with the history file.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Age after which a lock file is taken to be left by a writer that died.
+///
+/// Holders refresh the lock file's modification time every
+/// [`LOCK_HEARTBEAT`], so a slow prune is never taken for a dead one.
+const STALE_LOCK: Duration = Duration::from_secs(60);
+
+/// How often a held lock file is touched.
+const LOCK_HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// Runs recorded by this process, to make record ids unique.
+static RECORDED: AtomicUsize = AtomicUsize::new(0);
+
+/// A file of run records.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    max_value_bytes: usize,
+}
+
+/// One recorded run of a workflow.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Unique id of the run
+    pub id: String,
+
+    /// Workflow name
+    pub workflow: String,
+
+    /// Hash of the workflow definition, to tell edited workflows apart
+    pub definition_hash: String,
+
+    /// Inputs the run was given
+    #[serde(default)]
+    pub inputs: IndexMap<String, Value>,
+
+    /// When the run started
+    pub started_at: DateTime<Local>,
+
+    /// When the run ended
+    pub finished_at: DateTime<Local>,
+
+    /// Run time in milliseconds
+    pub duration_ms: f64,
+
+    /// How the run ended
+    pub status: RunStatus,
+
+    /// Steps that ran, ending with the failed step of a failed run
+    #[serde(default)]
+    pub steps: Vec<StepRecord>,
+
+    /// Final result of a successful run
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub result: Value,
+
+    /// Error message of a failed run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A step of a recorded run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepRecord {
+    /// Step index (0-based)
+    pub index: usize,
+
+    /// What the step runs: its method, `transform` or `workflow <target>`
+    pub runs: String,
+
+    /// Step id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// Whether the step succeeded
+    pub status: RunStatus,
+
+    /// Run time in milliseconds (not known for the failed step)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<f64>,
+
+    /// Resolved params, possibly shortened
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+
+    /// Step result, possibly shortened
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub result: Value,
+
+    /// Steps run by a workflow step
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<StepRecord>,
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    /// Every step completed.
+    Succeeded,
+
+    /// The workflow failed.
+    Failed,
+
+    /// The run was skipped because the previous run was still going.
+    Skipped,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RunStatus::Succeeded => "succeeded",
+            RunStatus::Failed => "failed",
+            RunStatus::Skipped => "skipped",
+        })
+    }
+}
+
+/// Which runs to list.
+#[derive(Debug, Clone, Default)]
+pub struct RunFilter {
+    workflow: Option<String>,
+    status: Option<RunStatus>,
+    since: Option<DateTime<Local>>,
+    until: Option<DateTime<Local>>,
+    limit: Option<usize>,
+}
+
+impl RunFilter {
+    /// Match every run.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only runs of this workflow.
+    pub fn workflow(mut self, name: &str) -> Self {
+        self.workflow = Some(name.to_string());
+        self
+    }
+
+    /// Only runs that ended this way.
+    pub fn status(mut self, status: RunStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only runs started at or after `since`.
+    pub fn since(mut self, since: DateTime<Local>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only runs started before `until`.
+    pub fn until(mut self, until: DateTime<Local>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// At most this many runs (the most recent).
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn matches(&self, run: &RunRecord) -> bool {
+        self.workflow.as_ref().is_none_or(|w| *w == run.workflow)
+            && self.status.is_none_or(|s| s == run.status)
+            && self.since.is_none_or(|since| run.started_at >= since)
+            && self.until.is_none_or(|until| run.started_at < until)
+    }
+}
+
+impl History {
+    /// Use the history file at `path`. It is created on the first record.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_value_bytes: MAX_VALUE_BYTES,
+        }
+    }
+
+    /// The default history file, `~/.fgp/history/runs.jsonl`.
+    pub fn default_path() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME")?;
+        Some(
+            PathBuf::from(home)
+                .join(".fgp")
+                .join("history")
+                .join("runs.jsonl"),
+        )
+    }
+
+    /// Shorten recorded params and results larger than `bytes` as JSON.
+    pub fn max_value_bytes(mut self, bytes: usize) -> Self {
+        self.max_value_bytes = bytes;
+        self
+    }
+
+    /// The history file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a finished run.
+    pub fn record(
+        &self,
+        workflow: &Workflow,
+        inputs: &IndexMap<String, Value>,
+        started_at: DateTime<Local>,
+        outcome: Result<&ExecutionResult, &anyhow::Error>,
+    ) -> Result<RunRecord> {
+        let record = RunRecord::new(workflow, inputs, started_at, outcome, self.max_value_bytes);
+        self.append(&record)?;
+        Ok(record)
+    }
+
+    /// Record a finished run like [`record`](Self::record), logging a
+    /// warning instead of failing when the file can't be written, so a
+    /// broken history never fails the run itself.
+    pub fn record_or_warn(
+        &self,
+        workflow: &Workflow,
+        inputs: &IndexMap<String, Value>,
+        started_at: DateTime<Local>,
+        outcome: Result<&ExecutionResult, &anyhow::Error>,
+    ) -> Option<RunRecord> {
+        self.record(workflow, inputs, started_at, outcome)
+            .inspect_err(|error| {
+                tracing::warn!(workflow = %workflow.name, error = %format!("{:#}", error), "Failed to record run");
+            })
+            .ok()
+    }
+
+    /// Append a record to the file.
+    pub fn append(&self, record: &RunRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        let _lock = self.lock()?;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to write history: {}", self.path.display()))
+    }
+
+    /// Runs matching a filter, most recent first.
+    pub fn list(&self, filter: &RunFilter) -> Result<Vec<RunRecord>> {
+        let mut runs: Vec<RunRecord> = self
+            .read()?
+            .into_iter()
+            .filter(|run| filter.matches(run))
+            .collect();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+        if let Some(limit) = filter.limit {
+            runs.truncate(limit);
+        }
+        Ok(runs)
+    }
+
+    /// Find a run by id.
+    pub fn get(&self, id: &str) -> Result<Option<RunRecord>> {
+        Ok(self.read()?.into_iter().find(|run| run.id == id))
+    }
+
+    /// Keep only the `keep` most recent runs. Returns the number removed.
+    ///
+    /// Runs recorded meanwhile, by this or another process, wait for the
+    /// file to be rewritten rather than being lost.
+    pub fn prune(&self, keep: usize) -> Result<usize> {
+        let _lock = self.lock()?;
+        let mut runs = self.read()?;
+        if runs.len() <= keep {
+            return Ok(0);
+        }
+        runs.sort_by_key(|run| run.started_at);
+        let removed = runs.len() - keep;
+
+        let mut content = String::new();
+        for run in &runs[removed..] {
+            content.push_str(&serde_json::to_string(run)?);
+            content.push('\n');
+        }
+
+        // Replace the file in one step so readers never see half of it
+        let temporary = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&temporary, content)
+            .and_then(|()| std::fs::rename(&temporary, &self.path))
+            .with_context(|| format!("Failed to write history: {}", self.path.display()))?;
+        Ok(removed)
+    }
+
+    /// Take the lock that writers of the file hold, waiting up to
+    /// [`LOCK_TIMEOUT`] for it.
+    fn lock(&self) -> Result<FileLock> {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".lock");
+        FileLock::acquire(PathBuf::from(path))
+            .with_context(|| format!("Failed to lock history: {}", self.path.display()))
+    }
+
+    /// Every record in the file, in file order. Lines that don't parse are
+    /// skipped.
+    fn read(&self) -> Result<Vec<RunRecord>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read history: {}", self.path.display()))
+            }
+        };
+
+        let mut runs = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line =
+                line.with_context(|| format!("Failed to read history: {}", self.path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(run) => runs.push(run),
+                Err(e) => tracing::warn!(
+                    file = %self.path.display(),
+                    line = number + 1,
+                    error = %e,
+                    "Skipping unreadable history record"
+                ),
+            }
+        }
+        Ok(runs)
+    }
+}
+
+/// A lock file, created exclusively and removed when dropped.
+///
+/// While held, a thread refreshes its modification time so other writers
+/// don't mistake it for stale.
+struct FileLock {
+    path: PathBuf,
+
+    /// Dropped to stop the heartbeat thread
+    stop: Option<mpsc::Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+impl FileLock {
+    fn acquire(path: PathBuf) -> std::io::Result<Self> {
+        let start = Instant::now();
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => {
+                    let (stop, stopped) = mpsc::channel::<()>();
+                    let heartbeat = std::thread::spawn(move || {
+                        while let Err(mpsc::RecvTimeoutError::Timeout) =
+                            stopped.recv_timeout(LOCK_HEARTBEAT)
+                        {
+                            let _ = file.set_modified(SystemTime::now());
+                        }
+                    });
+                    return Ok(Self {
+                        path,
+                        stop: Some(stop),
+                        heartbeat: Some(heartbeat),
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+
+            let stale = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > STALE_LOCK);
+            if stale {
+                let _ = std::fs::remove_file(&path);
+            } else if start.elapsed() > LOCK_TIMEOUT {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("{} is held by another writer", path.display()),
+                ));
+            } else {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl RunRecord {
+    /// Record of a finished run, shortening params and results larger than
+    /// `max_value_bytes`.
+    pub fn new(
+        workflow: &Workflow,
+        inputs: &IndexMap<String, Value>,
+        started_at: DateTime<Local>,
+        outcome: Result<&ExecutionResult, &anyhow::Error>,
+        max_value_bytes: usize,
+    ) -> Self {
+        let finished_at = Local::now();
+        let sequence = RECORDED.fetch_add(1, Ordering::Relaxed);
+
+        let mut record = RunRecord {
+            id: format!(
+                "{}-{}-{}",
+                started_at.format("%Y%m%dT%H%M%S%.3f"),
+                std::process::id(),
+                sequence
+            ),
+            workflow: workflow.name.clone(),
+            definition_hash: definition_hash(workflow),
+            inputs: inputs.clone(),
+            started_at,
+            finished_at,
+            duration_ms: (finished_at - started_at).num_microseconds().unwrap_or(0) as f64 / 1000.0,
+            status: RunStatus::Succeeded,
+            steps: Vec::new(),
+            result: Value::Null,
+            error: None,
+        };
+
+        match outcome {
+            Ok(result) => {
+                record.duration_ms = result.total_ms;
+                record.steps = step_records(&result.step_results, max_value_bytes);
+                record.result = shorten(&result.result, max_value_bytes);
+            }
+            Err(error) => {
+                record.status = RunStatus::Failed;
+                record.error = Some(format!("{:#}", error));
+                if let Some(failure) = error.downcast_ref::<StepFailure>() {
+                    record.steps = failure_records(failure, max_value_bytes);
+                }
+            }
+        }
+
+        record
+    }
+
+    /// The step that failed, for a failed run.
+    pub fn failed_step(&self) -> Option<&StepRecord> {
+        self.steps
+            .iter()
+            .find(|step| step.status == RunStatus::Failed)
+    }
+}
+
+/// One line per run:
+///
+/// ```text
+/// 2026-10-18 08:00:00 digest: failed (2 steps, 812.4 ms) [20261018T080000.013-4242-0]: Step 1 (gmail.inbox) failed: ...
+/// ```
+impl fmt::Display for RunRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} ({} steps, {:.1} ms) [{}]",
+            self.started_at.format("%Y-%m-%d %H:%M:%S"),
+            self.workflow,
+            self.status,
+            self.steps.len(),
+            self.duration_ms,
+            self.id
+        )?;
+        match self.error {
+            Some(ref error) => write!(f, ": {}", error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Records of the steps before a failure, ending with the failed step, whose
+/// children are the steps of its failed sub-workflow.
+fn failure_records(failure: &StepFailure, max_value_bytes: usize) -> Vec<StepRecord> {
+    let mut records = step_records(&failure.step_results, max_value_bytes);
+    records.push(StepRecord {
+        index: failure.index,
+        runs: failure.step.label(),
+        id: failure.step.id.clone(),
+        status: RunStatus::Failed,
+        duration_ms: None,
+        params: failure
+            .params
+            .as_ref()
+            .map_or(Value::Null, |params| shorten(params, max_value_bytes)),
+        result: Value::Null,
+        children: failure
+            .child
+            .as_ref()
+            .map(|child| failure_records(child, max_value_bytes))
+            .unwrap_or_default(),
+    });
+    records
+}
+
+fn step_records(steps: &[StepResult], max_value_bytes: usize) -> Vec<StepRecord> {
+    steps
+        .iter()
+        .map(|step| StepRecord {
+            index: step.index,
+            runs: step.step.label(),
+            id: step.step.id.clone(),
+            status: RunStatus::Succeeded,
+            duration_ms: Some(step.duration_ms),
+            params: shorten(&step.params, max_value_bytes),
+            result: shorten(&step.result, max_value_bytes),
+            children: step_records(&step.children, max_value_bytes),
+        })
+        .collect()
+}
+
+/// A value, or a preview of it if its JSON is longer than `max_bytes`.
+fn shorten(value: &Value, max_bytes: usize) -> Value {
+    let text = value.to_string();
+    if text.len() <= max_bytes {
+        return value.clone();
+    }
+
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    json!({
+        "truncated": true,
+        "bytes": text.len(),
+        "preview": &text[..end],
+    })
+}
+
+/// Stable hash (64-bit FNV-1a, in hex) of a workflow's definition.
+fn definition_hash(workflow: &Workflow) -> String {
+    let definition = serde_json::to_string(workflow).unwrap_or_default();
+    let hash = definition
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use crate::{execute, Step};
+
+    /// A history in a scratch directory, removed when the directory drops.
+    fn history() -> (History, TempDir) {
+        let dir = TempDir::new("history");
+        (History::open(dir.join("runs.jsonl")), dir)
+    }
+
+    /// Ids of runs (durations don't survive a JSON round trip exactly).
+    fn ids(runs: &[RunRecord]) -> Vec<&str> {
+        runs.iter().map(|run| run.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_record_and_list() {
+        let (history, _dir) = history();
+        let history = history.max_value_bytes(20);
+        let digest = Workflow::new("digest")
+            .add(Step::transform(serde_json::json!({"body": "x".repeat(50)})))
+            .add(Step::transform("{{ prev.body }}").id("copy"))
+            .build();
+        let broken = Workflow::new("digest")
+            .add(Step::transform(1))
+            .add(Step::workflow("missing").with_param("account", "{{ prev }}"))
+            .build();
+        let inputs = IndexMap::from([("account".to_string(), Value::from("work"))]);
+
+        let started_at = Local::now();
+        let first = history
+            .record(&digest, &inputs, started_at, execute(&digest).as_ref())
+            .unwrap();
+        let second = history
+            .record(
+                &broken,
+                &IndexMap::new(),
+                started_at + chrono::Duration::seconds(1),
+                execute(&broken).as_ref(),
+            )
+            .unwrap();
+
+        assert_ne!(first.id, second.id);
+        assert_ne!(first.definition_hash, second.definition_hash);
+        assert_eq!(first.definition_hash, definition_hash(&digest));
+        assert_eq!(first.steps[1].id.as_deref(), Some("copy"));
+        assert_eq!(first.steps[0].result["truncated"], true);
+        assert_eq!(first.steps[0].result["preview"], "{\"body\":\"xxxxxxxxxxx");
+
+        let runs = history.list(&RunFilter::new()).unwrap();
+        assert_eq!(ids(&runs), vec![second.id.as_str(), first.id.as_str()]);
+        assert_eq!(runs[1].inputs, inputs);
+
+        let failures = history
+            .list(
+                &RunFilter::new()
+                    .workflow("digest")
+                    .status(RunStatus::Failed),
+            )
+            .unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].error.as_deref(),
+            Some("Step 1 (workflow missing) failed: Workflow 'missing' is not registered")
+        );
+        assert_eq!(failures[0].failed_step().unwrap().index, 1);
+        assert_eq!(
+            failures[0].failed_step().unwrap().params,
+            serde_json::json!({"account": 1})
+        );
+        assert_eq!(failures[0].steps[0].result, Value::from(1));
+
+        let recent = history
+            .list(&RunFilter::new().since(started_at + chrono::Duration::milliseconds(500)))
+            .unwrap();
+        assert_eq!(ids(&recent), vec![second.id.as_str()]);
+        assert_eq!(history.list(&RunFilter::new().limit(1)).unwrap().len(), 1);
+        assert!(history
+            .list(&RunFilter::new().workflow("triage"))
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(history.get(&first.id).unwrap().unwrap().steps.len(), 2);
+        assert_eq!(history.get("nope").unwrap(), None);
+
+        assert_eq!(history.prune(1).unwrap(), 1);
+        let runs = history.list(&RunFilter::new()).unwrap();
+        assert_eq!(ids(&runs), vec![second.id.as_str()]);
+    }
+
+    #[test]
+    fn test_prune_keeps_concurrent_records() {
+        let (history, _dir) = history();
+        let workflow = Workflow::new("w").add(Step::transform(1)).build();
+        let result = execute(&workflow).unwrap();
+        let record = |started_at| {
+            history
+                .record(&workflow, &IndexMap::new(), started_at, Ok(&result))
+                .unwrap()
+                .id
+        };
+        for _ in 0..100 {
+            record(Local::now() - chrono::Duration::days(1));
+        }
+
+        let recorded: Vec<String> = std::thread::scope(|scope| {
+            let writers: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| (0..25).map(|_| record(Local::now())).collect::<Vec<_>>()))
+                .collect();
+            // Pause longer than writers do between tries, so they get a turn
+            while !writers.iter().all(|writer| writer.is_finished()) {
+                history.prune(100).unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            writers
+                .into_iter()
+                .flat_map(|writer| writer.join().unwrap())
+                .collect()
+        });
+
+        let kept = history.list(&RunFilter::new()).unwrap();
+        for id in &recorded {
+            assert!(kept.iter().any(|run| run.id == *id), "lost {}", id);
+        }
+    }
+
+    #[test]
+    fn test_unreadable_lines_are_skipped() {
+        let (history, _dir) = history();
+        assert!(history.list(&RunFilter::new()).unwrap().is_empty());
+
+        let workflow = Workflow::new("w").add(Step::transform(1)).build();
+        history
+            .record(
+                &workflow,
+                &IndexMap::new(),
+                Local::now(),
+                execute(&workflow).as_ref(),
+            )
+            .unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(history.path())
+            .unwrap();
+        writeln!(file, "{{not json").unwrap();
+
+        assert_eq!(history.list(&RunFilter::new()).unwrap().len(), 1);
+    }
+}
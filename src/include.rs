@@ -0,0 +1,553 @@
+//! Workflow includes and imports.
+//!
+//! A step entry `include: <target>` is replaced by other steps when a
+//! workflow file is loaded:
+//!
+//! - `include: prelude.yaml` splices all steps of another workflow file
+//! - `include: common.yaml#fetch-inbox` splices a named fragment of a file
+//! - `include: fetch-inbox` splices a fragment defined in this file's
+//!   `fragments:` or in a file listed under `import:`
+//!
+//! ```yaml
+//! # common.yaml
+//! fragments:
+//!   fetch-inbox:
+//!     - service: gmail
+//!       method: gmail.inbox
+//!       output: emails
+//!
+//! # digest.yaml
+//! name: digest
+//! import:
+//!   - common.yaml
+//! steps:
+//!   - include: fetch-inbox
+//!   - service: browser
+//!     method: browser.open
+//!     params:
+//!       url: "{{ emails.0.url }}"
+//! ```
+//!
+//! Paths are relative to the file containing the include.
+
+use crate::diagnostic::{in_file, Origin, SOURCE_KEY};
+use crate::format::{mark_templates_explicitly, parse_document, Format};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde_yaml::{Mapping, Sequence, Value};
+use std::path::{Path, PathBuf};
+
+/// Load a workflow file and resolve its includes, adding each file it reads
+/// to `files`.
+///
+/// Files are added even when loading fails, including a missing include.
+pub(crate) fn load(path: &Path, format: Format, files: &mut Vec<PathBuf>) -> Result<Value> {
+    let mut resolver = Resolver::default();
+    let loaded = resolver.load(path, format, true);
+    for file in resolver.files {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    let mut doc = loaded?;
+
+    // Resolved fragments and imports are no longer needed
+    if let Some(map) = doc.as_mapping_mut() {
+        map.remove("import");
+        map.remove("fragments");
+    }
+
+    Ok(doc)
+}
+
+/// Record on each step where it was defined, so diagnostics can point into
+/// the right file after steps are spliced into another workflow.
+fn record_origins(doc: &mut Value, path: &Path) {
+    let mut lists: Vec<(String, &mut Value)> = Vec::new();
+    let Some(map) = doc.as_mapping_mut() else {
+        return;
+    };
+    for (key, value) in map.iter_mut() {
+        match key.as_str() {
+            Some("steps") => lists.push(("steps".to_string(), value)),
+            Some("fragments") => {
+                for (name, steps) in value.as_mapping_mut().into_iter().flatten() {
+                    let name = name.as_str().unwrap_or_default();
+                    lists.push((format!("fragments.{}", name), steps));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (prefix, steps) in lists {
+        for (i, step) in steps.as_sequence_mut().into_iter().flatten().enumerate() {
+            if let Some(step) = step.as_mapping_mut() {
+                if !step.contains_key("include") {
+                    let origin = Origin::to_value(path, &format!("{}.{}", prefix, i));
+                    step.insert(Value::from(SOURCE_KEY), origin);
+                }
+            }
+        }
+    }
+}
+
+/// What an include entry refers to.
+enum Target<'a> {
+    /// All steps of a file
+    File(&'a str),
+
+    /// A named fragment of a file
+    FileFragment(&'a str, &'a str),
+
+    /// A fragment in scope (own or imported)
+    Fragment(&'a str),
+}
+
+impl<'a> Target<'a> {
+    fn parse(target: &'a str) -> Self {
+        if let Some((file, name)) = target.split_once('#') {
+            Target::FileFragment(file, name)
+        } else if Format::from_path(target).is_some() {
+            Target::File(target)
+        } else {
+            Target::Fragment(target)
+        }
+    }
+}
+
+/// One link in the chain of files and fragments being resolved.
+#[derive(PartialEq)]
+struct Link {
+    file: PathBuf,
+    fragment: Option<String>,
+    label: String,
+}
+
+#[derive(Default)]
+struct Resolver {
+    chain: Vec<Link>,
+
+    /// Files read so far
+    files: Vec<PathBuf>,
+}
+
+impl Resolver {
+    /// Load a file, resolving includes in its steps and fragments.
+    fn load(&mut self, path: &Path, format: Format, root: bool) -> Result<Value> {
+        self.files.push(path.to_path_buf());
+        let canonical = std::fs::canonicalize(path)
+            .with_context(|| format!("Failed to read workflow file: {}", path.display()))?;
+        self.enter(canonical, None, path.display().to_string())?;
+
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read workflow file: {}", path.display()))?;
+        let mut doc = parse_document(&content, format).map_err(|e| in_file(e, path))?;
+        record_origins(&mut doc, path);
+
+        // Included steps keep the template mode of the file they come from
+        if !root {
+            mark_templates_explicitly(&mut doc)?;
+        }
+
+        let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let scope = self.scope(&doc, &base)?;
+
+        let mut fragments = Mapping::new();
+        for name in scope.own.keys() {
+            let steps = self.expand_fragment(name, &scope, &base)?;
+            fragments.insert(Value::String(name.clone()), Value::Sequence(steps));
+        }
+
+        if let Some(map) = doc.as_mapping_mut() {
+            if let Some(steps) = map.get("steps").and_then(Value::as_sequence) {
+                let steps = self.expand_steps(steps, &scope, &base)?;
+                map.insert(Value::from("steps"), Value::Sequence(steps));
+            }
+            if !fragments.is_empty() {
+                map.insert(Value::from("fragments"), Value::Mapping(fragments));
+            }
+        }
+
+        self.chain.pop();
+        Ok(doc)
+    }
+
+    /// Push a link onto the chain, failing if it is already being resolved.
+    fn enter(&mut self, file: PathBuf, fragment: Option<String>, label: String) -> Result<()> {
+        let link = Link {
+            file,
+            fragment,
+            label,
+        };
+
+        if self
+            .chain
+            .iter()
+            .any(|l| l.file == link.file && l.fragment == link.fragment)
+        {
+            anyhow::bail!("Include cycle: {} -> {}", self.chain_label(), link.label);
+        }
+
+        self.chain.push(link);
+        Ok(())
+    }
+
+    /// The include chain, e.g. `digest.yaml -> common.yaml#fetch-inbox`.
+    fn chain_label(&self) -> String {
+        self.chain
+            .iter()
+            .map(|l| l.label.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
+    /// Collect the fragments a file can include by name.
+    fn scope(&mut self, doc: &Value, base: &Path) -> Result<Scope> {
+        let mut scope = Scope::default();
+
+        if let Some(fragments) = doc.get("fragments") {
+            let fragments = fragments
+                .as_mapping()
+                .context("fragments must be a mapping of names to step lists")?;
+            for (name, steps) in fragments {
+                let name = name.as_str().context("Fragment names must be strings")?;
+                let steps = steps
+                    .as_sequence()
+                    .with_context(|| format!("Fragment '{}' must be a list of steps", name))?;
+                scope.own.insert(name.to_string(), steps.clone());
+            }
+        }
+
+        let imports = match doc.get("import") {
+            None => Vec::new(),
+            Some(Value::String(file)) => vec![file.clone()],
+            Some(Value::Sequence(files)) => files
+                .iter()
+                .map(|f| f.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .context("import must be a list of file paths")?,
+            Some(_) => anyhow::bail!("import must be a list of file paths"),
+        };
+
+        for file in imports {
+            let loaded = self.load_relative(&file, base)?;
+            let Some(fragments) = loaded.get("fragments").and_then(Value::as_mapping) else {
+                continue;
+            };
+
+            for (name, steps) in fragments {
+                let name = name.as_str().unwrap_or_default().to_string();
+                if scope.own.contains_key(&name) {
+                    continue;
+                }
+                if let Some((other, _)) = scope.imported.get(&name) {
+                    anyhow::bail!(
+                        "Fragment '{}' is imported from both {} and {}",
+                        name,
+                        other,
+                        file
+                    );
+                }
+                let steps = steps.as_sequence().cloned().unwrap_or_default();
+                scope.imported.insert(name, (file.clone(), steps));
+            }
+        }
+
+        Ok(scope)
+    }
+
+    /// Expand a fragment defined in the current file.
+    fn expand_fragment(&mut self, name: &str, scope: &Scope, base: &Path) -> Result<Sequence> {
+        let file = self
+            .chain
+            .last()
+            .map(|l| l.file.clone())
+            .unwrap_or_default();
+        let label = format!(
+            "{}#{}",
+            self.chain
+                .last()
+                .map(|l| l.label.as_str())
+                .unwrap_or_default(),
+            name
+        );
+        self.enter(file, Some(name.to_string()), label)?;
+
+        let steps = self.expand_steps(&scope.own[name], scope, base)?;
+
+        self.chain.pop();
+        Ok(steps)
+    }
+
+    /// Replace include entries in a list of steps.
+    fn expand_steps(&mut self, steps: &Sequence, scope: &Scope, base: &Path) -> Result<Sequence> {
+        let mut expanded = Sequence::new();
+
+        for step in steps {
+            let Some(target) = step.get("include") else {
+                expanded.push(step.clone());
+                continue;
+            };
+
+            let target = target
+                .as_str()
+                .context("include must be a file path or fragment name")?;
+            if step.as_mapping().map_or(0, Mapping::len) > 1 {
+                anyhow::bail!("Step including '{}' cannot have other fields", target);
+            }
+
+            let steps = self.include(target, scope, base).with_context(|| {
+                format!(
+                    "Failed to include '{}' (include chain: {})",
+                    target,
+                    self.chain_label()
+                )
+            })?;
+            expanded.extend(steps);
+        }
+
+        Ok(expanded)
+    }
+
+    /// Resolve one include target to its steps.
+    fn include(&mut self, target: &str, scope: &Scope, base: &Path) -> Result<Sequence> {
+        match Target::parse(target) {
+            Target::File(file) => {
+                let doc = self.load_relative(file, base)?;
+                Ok(doc
+                    .get("steps")
+                    .and_then(Value::as_sequence)
+                    .cloned()
+                    .unwrap_or_default())
+            }
+            Target::FileFragment(file, name) => {
+                let doc = self.load_relative(file, base)?;
+                doc.get("fragments")
+                    .and_then(|f| f.get(name))
+                    .and_then(Value::as_sequence)
+                    .cloned()
+                    .with_context(|| format!("Fragment '{}' not found in {}", name, file))
+            }
+            Target::Fragment(name) => {
+                if scope.own.contains_key(name) {
+                    self.expand_fragment(name, scope, base)
+                } else if let Some((_, steps)) = scope.imported.get(name) {
+                    Ok(steps.clone())
+                } else {
+                    anyhow::bail!("Fragment '{}' not found", name)
+                }
+            }
+        }
+    }
+
+    /// Load a file relative to the including file's directory.
+    fn load_relative(&mut self, file: &str, base: &Path) -> Result<Value> {
+        let path = base.join(file);
+        let format = Format::from_path_or_err(&path)?;
+        self.load(&path, format, false)
+    }
+}
+
+/// Fragments available to a file.
+#[derive(Default)]
+struct Scope {
+    /// Fragments defined in the file itself (not yet expanded)
+    own: IndexMap<String, Sequence>,
+
+    /// Fragments from imported files (already expanded), with their source
+    imported: IndexMap<String, (String, Sequence)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// Write files into a fresh temporary directory.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(&format!("include-{}", test));
+        for (name, content) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include_file_and_fragments() {
+        let dir = write_files(
+            "basic",
+            &[
+                (
+                    "digest.yaml",
+                    r#"
+name: digest
+import:
+  - lib/common.yaml
+fragments:
+  open:
+    - service: browser
+      method: browser.open
+      params:
+        url: "{{ emails.0.url }}"
+steps:
+  - include: fetch-inbox
+  - include: lib/prelude.yaml
+  - include: lib/common.yaml#summarize
+  - include: open
+"#,
+                ),
+                (
+                    "lib/common.yaml",
+                    r#"
+fragments:
+  fetch-inbox:
+    - service: gmail
+      method: gmail.inbox
+      output: emails
+  summarize:
+    - include: prelude.yaml
+    - set: "{{ emails }}"
+"#,
+                ),
+                (
+                    "lib/prelude.yaml",
+                    r#"
+name: prelude
+steps:
+  - service: gmail
+    method: gmail.unread
+"#,
+                ),
+            ],
+        );
+
+        let doc = load(&dir.join("digest.yaml"), Format::Yaml, &mut Vec::new()).unwrap();
+        let locator = crate::diagnostic::Locator::file(&dir.join("digest.yaml"));
+        let workflow = crate::format::from_document(doc, Format::Yaml, locator).unwrap();
+
+        let methods: Vec<&str> = workflow.steps.iter().map(|s| s.method.as_str()).collect();
+        assert_eq!(
+            methods,
+            [
+                "gmail.inbox",
+                "gmail.unread",
+                "gmail.unread",
+                "",
+                "browser.open"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_cycle_shows_chain() {
+        let dir = write_files(
+            "cycle",
+            &[
+                ("a.yaml", "name: a\nsteps:\n  - include: b.yaml\n"),
+                ("b.yaml", "name: b\nsteps:\n  - include: a.yaml\n"),
+            ],
+        );
+
+        let err = load(&dir.join("a.yaml"), Format::Yaml, &mut Vec::new())
+            .err()
+            .unwrap();
+        let message = format!("{:#}", err);
+        assert!(message.contains("Include cycle:"), "{}", message);
+        assert!(message.contains("a.yaml -> "), "{}", message);
+        assert!(message.contains("b.yaml -> "), "{}", message);
+    }
+
+    #[test]
+    fn test_fragment_cycle() {
+        let dir = write_files(
+            "fragment-cycle",
+            &[(
+                "w.yaml",
+                r#"
+name: w
+fragments:
+  one:
+    - include: two
+  two:
+    - include: one
+steps:
+  - include: one
+"#,
+            )],
+        );
+
+        let err = load(&dir.join("w.yaml"), Format::Yaml, &mut Vec::new())
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", err).contains("w.yaml#one -> "));
+    }
+
+    #[test]
+    fn test_included_template_mode() {
+        let dir = write_files(
+            "modes",
+            &[
+                (
+                    "root.yaml",
+                    r#"
+name: root
+templates: explicit
+steps:
+  - include: auto.json
+"#,
+                ),
+                (
+                    "auto.json",
+                    r#"{"name": "auto", "steps": [{"set": "{{ x }}"}]}"#,
+                ),
+            ],
+        );
+
+        let workflow = crate::load_file(dir.join("root.yaml")).unwrap();
+        assert_eq!(
+            workflow.steps[0].transform,
+            Some(crate::Param::template("{{ x }}"))
+        );
+    }
+
+    #[test]
+    fn test_missing_fragment() {
+        let dir = write_files(
+            "missing",
+            &[("w.yaml", "name: w\nsteps:\n  - include: nope\n")],
+        );
+
+        let err = crate::load_file(dir.join("w.yaml")).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("Fragment 'nope' not found"), "{}", message);
+    }
+
+    #[test]
+    fn test_error_in_included_file_is_located() {
+        let dir = write_files(
+            "located",
+            &[
+                (
+                    "main.yaml",
+                    "name: main\nimport: lib.yaml\nsteps:\n  - include: fetch\n",
+                ),
+                (
+                    "lib.yaml",
+                    "fragments:\n  fetch:\n    - service: gmail\n      method: \"gmail inbox\"\n",
+                ),
+            ],
+        );
+
+        let err = crate::load_file(dir.join("main.yaml")).unwrap_err();
+        let diagnostic = err.downcast_ref::<crate::diagnostic::Diagnostic>().unwrap();
+
+        assert_eq!(diagnostic.file, Some(dir.join("lib.yaml")));
+        assert_eq!(
+            diagnostic.location.map(|l| (l.line, l.column)),
+            Some((4, 15))
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! JSON workflow parser.
+
+use crate::format::{load_file_as, parse_str, to_document, Format};
+use crate::Workflow;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Parse a workflow from JSON string.
+///
+/// Templates are `"{{ ... }}"` strings or `{"__template__": "..."}` objects;
+/// literals are wrapped as `{"__raw__": ...}`.
+///
+/// # Example
+///
+/// ```rust
+/// use fgp_workflow::json::parse_json;
+///
+/// let json = r#"{
+///   "name": "my-workflow",
+///   "steps": [
+///     {"service": "gmail", "method": "gmail.inbox", "params": {"limit": 5}, "output": "emails"}
+///   ]
+/// }"#;
+///
+/// let workflow = parse_json(json).unwrap();
+/// assert_eq!(workflow.name, "my-workflow");
+/// ```
+pub fn parse_json(json: &str) -> Result<Workflow> {
+    parse_str(json, Format::Json)
+}
+
+/// Load and parse a workflow from a JSON file.
+pub fn load_file(path: impl AsRef<Path>) -> Result<Workflow> {
+    load_file_as(path.as_ref(), Format::Json)
+}
+
+/// Serialize a workflow to pretty-printed JSON.
+///
+/// Templates are bare `"{{ ... }}"` strings (`{"__template__": ...}` in
+/// explicit mode) and literals containing template syntax are wrapped as
+/// `{"__raw__": ...}`, so [`parse_json`] reads the result back identically.
+pub fn to_json(workflow: &Workflow) -> Result<String> {
+    let doc = to_document(workflow, false)?;
+    serde_json::to_string_pretty(&doc).context("Failed to serialize workflow JSON")
+}
+
+/// Save a workflow to a JSON file.
+pub fn save_file(workflow: &Workflow, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let json = to_json(workflow)?;
+
+    std::fs::write(path, json)
+        .with_context(|| format!("Failed to write workflow file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Param, Step, TemplateMode};
+
+    #[test]
+    fn test_parse_json_markers() {
+        let json = r#"{
+  "name": "markers",
+  "steps": [{
+    "service": "render",
+    "method": "render.mustache",
+    "params": {
+      "body": {"__raw__": "Hello {{ name }}"},
+      "to": {"__template__": "{{ user.email }}"}
+    }
+  }]
+}"#;
+
+        let workflow = parse_json(json).unwrap();
+        let params = &workflow.steps[0].params;
+        assert_eq!(
+            params.get("body"),
+            Some(&Param::literal("Hello {{ name }}"))
+        );
+        assert_eq!(params.get("to"), Some(&Param::template("{{ user.email }}")));
+    }
+
+    #[test]
+    fn test_json_explicit_round_trip() {
+        let workflow = Workflow::new("explicit")
+            .templates(TemplateMode::Explicit)
+            .add(
+                Step::call("render", "render.mustache")
+                    .with_raw_param("body", "Hello {{ name }}")
+                    .with_template_param("to", "{{ user.email }}"),
+            )
+            .build();
+
+        let json = to_json(&workflow).unwrap();
+
+        assert!(json.contains(r#""__template__": "{{ user.email }}""#));
+        assert_eq!(parse_json(&json).unwrap(), workflow);
+    }
+}
@@ -35,18 +35,198 @@
 //!     params:
 //!       url: "{{ emails.0.url }}"
 //! ```
+//!
+//! The same workflow can be written in JSON or TOML; [`load_file`] picks
+//! the format from the file extension.
+//!
+//! ## Transform Steps
+//!
+//! Steps with `transform` (or `set`) compute a value from the context
+//! without calling a daemon:
+//!
+//! ```yaml
+//! - set:
+//!     first: "{{ emails.0 }}"
+//!     total: "{{ inbox.total }}"
+//!   output: summary
+//! ```
+//!
+//! ## Rendered Values
+//!
+//! A template rendering to valid JSON becomes that value, so `"{{ total }}"`
+//! holding `"2"` gives the number `2`. A template that is only a reference
+//! to an object or array, like `"{{ emails.0 }}"`, gives the value itself.
+//! Output is not HTML-escaped: `&` and `<` come through as they are.
+//!
+//! Earlier versions rendered objects and arrays as `[object]` and escaped
+//! text as HTML (`&amp;`, `&lt;`); workflows that undid the escaping should
+//! stop doing so.
+//!
+//! ## Step References
+//!
+//! Steps with an `id` expose their `result`, `status`, `duration_ms` and
+//! resolved `params` to later templates, regardless of position:
+//!
+//! ```yaml
+//! - id: inbox
+//!   service: gmail
+//!   method: gmail.inbox
+//! - service: browser
+//!   method: browser.open
+//!   params:
+//!     url: "{{ steps.inbox.result.0.url }}"
+//! ```
+//!
+//! ## Sub-workflows
+//!
+//! A step with `workflow` runs another workflow, given by a name registered
+//! with [`Context::with_workflow`] or a file path. Its params become the
+//! child's inputs, it runs in a fresh context, and its declared `outputs`
+//! become the step result:
+//!
+//! ```yaml
+//! # fetch-inbox.yaml
+//! name: fetch-inbox
+//! inputs:
+//!   account:
+//!     default: personal
+//! outputs:
+//!   emails: "{{ inbox.messages }}"
+//! steps:
+//!   - service: "gmail-{{ account }}"
+//!     method: gmail.inbox
+//!     output: inbox
+//!
+//! # digest.yaml
+//! - workflow: fetch-inbox.yaml
+//!   params:
+//!     account: work
+//!   output: fetched
+//! ```
+//!
+//! Workflows can nest up to [`MAX_WORKFLOW_DEPTH`] levels.
+//!
+//! ## Validation
+//!
+//! [`Workflow::validate`] checks a workflow without contacting any daemon
+//! and returns [`diagnostic::Diagnostic`]s with a severity and, for
+//! workflows loaded from files, a `file:line:column` location:
+//!
+//! ```rust,no_run
+//! let workflow = fgp_workflow::load_file("workflows/digest.yaml")?;
+//! for diagnostic in workflow.validate() {
+//!     eprintln!("{}", diagnostic);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! ## Environment and Secrets
+//!
+//! Templates can read `{{ env.NAME }}` and `{{ secrets.NAME }}`. Secrets come
+//! from a [`secrets::SecretProvider`] (environment variables by default) and
+//! are masked as `***` in step results, the final context, logs and errors.
+//!
+//! ## Literal Braces
+//!
+//! Mark a value `!raw` to pass `{{ ... }}` through untouched, or set
+//! `templates: explicit` so only `!template` values are rendered:
+//!
+//! ```yaml
+//! templates: explicit
+//! steps:
+//!   - service: render
+//!     method: render.mustache
+//!     params:
+//!       body: "Hello {{ name }}"        # literal
+//!       to: !template "{{ user.email }}" # rendered
+//! ```
+//!
+//! ## Includes
+//!
+//! Workflow files can reuse steps from other files. `include:` splices in
+//! another file's steps, a named fragment (`common.yaml#fetch-inbox`), or a
+//! fragment from this file's `fragments:` or an `import:`ed file:
+//!
+//! ```yaml
+//! import:
+//!   - common.yaml
+//! steps:
+//!   - include: fetch-inbox
+//!   - include: notify.yaml
+//! ```
+//!
+//! ## Schedules
+//!
+//! A `schedule:` cron expression makes a workflow recurring; a
+//! [`schedule::Scheduler`] runs scheduled workflows in-process with a
+//! policy for runs that overlap:
+//!
+//! ```yaml
+//! name: morning-digest
+//! schedule: "0 8 * * 1-5"
+//! ```
+//!
+//! ## Command Line
+//!
+//! The `fgp-workflow` binary (the default `cli` feature) runs, validates,
+//! plans and graphs workflow files:
+//!
+//! ```text
+//! fgp-workflow run digest.yaml --input account=work
+//! fgp-workflow plan digest.yaml --input account=work --output json
+//! fgp-workflow schedule digest.yaml triage.yaml
+//! fgp-workflow history digest --failed
+//! fgp-workflow list workflows/
+//! fgp-workflow serve workflows/
+//! ```
+//!
+//! A plan is a [`dry_run`]: transforms are evaluated and params rendered,
+//! but no service is called. With `--watch`, the workflow runs (or is
+//! planned) again on every save of its file or includes; see [`watch`].
+//!
+//! `run`, `schedule` and `serve` record each run in the run history under
+//! `~/.fgp/history`, which `history` lists; see [`history`]. `run --report`
+//! also saves a versioned JSON [`report`] of the run, and `--junit` and
+//! `--markdown` render it for CI and pull requests. `list` shows
+//! the workflows found in directories and `~/.fgp/workflows` (see
+//! [`registry`]), and `serve` makes them callable by other FGP clients as
+//! the `workflow` daemon (see [`service`]).
 
 mod context;
+pub mod diagnostic;
 mod executor;
+pub mod format;
+pub mod graph;
+pub mod history;
+mod include;
+pub mod json;
+pub mod manifest;
+mod param;
+pub mod registry;
+pub mod report;
+pub mod schedule;
+pub mod schema;
+pub mod secrets;
+pub mod service;
 mod step;
+#[cfg(test)]
+mod testing;
+pub mod toml;
+mod validate;
+pub mod watch;
 mod workflow;
 pub mod yaml;
 
 pub use context::Context;
-pub use executor::{execute, ExecutionResult};
-pub use step::{Step, StepBuilder};
-pub use workflow::{Workflow, WorkflowBuilder};
-pub use yaml::parse_yaml;
+pub use executor::{
+    dry_run, execute, execute_with_context, ExecutionResult, StepFailure, StepResult,
+    MAX_WORKFLOW_DEPTH,
+};
+pub use format::{from_str_with_format, load_file, Format};
+pub use param::Param;
+pub use step::{Output, Step, StepBuilder};
+pub use workflow::{Input, TemplateMode, Workflow, WorkflowBuilder};
+pub use yaml::{parse_yaml, to_yaml};
 
 /// Re-export common types
 pub use serde_json::Value;
@@ -0,0 +1,477 @@
+//! Service method manifests.
+//!
+//! A manifest lists the methods a daemon service provides and their params,
+//! in the shape of the daemon's own method listing:
+//!
+//! ```yaml
+//! service: gmail
+//! methods:
+//!   - name: gmail.inbox
+//!     description: List inbox messages
+//!     params:
+//!       - name: limit
+//!         type: integer
+//!         required: false
+//!   - name: gmail.send
+//!     params:
+//!       - name: to
+//!         type: string
+//!         required: true
+//! ```
+//!
+//! [`Workflow::validate_with`](crate::Workflow::validate_with) checks each
+//! step's method and params against the manifests of its service.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::step::is_template;
+use crate::{Param, Step, Workflow};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Methods provided by one service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceManifest {
+    /// Service name (defaults to the manifest's file name)
+    #[serde(default)]
+    pub service: String,
+
+    /// Methods the service provides
+    pub methods: Vec<MethodSpec>,
+}
+
+/// A method in a service manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MethodSpec {
+    /// Method name, with or without the service prefix
+    pub name: String,
+
+    /// What the method does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Params the method accepts
+    #[serde(default)]
+    pub params: Vec<ParamSpec>,
+}
+
+/// A method param in a service manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParamSpec {
+    /// Param name
+    pub name: String,
+
+    /// Expected type: `string`, `integer`, `number`, `boolean`, `array`,
+    /// `object` or `any`
+    #[serde(rename = "type", alias = "param_type", default)]
+    pub param_type: String,
+
+    /// Whether the param must be given
+    #[serde(default)]
+    pub required: bool,
+
+    /// Value used when the param is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+}
+
+impl ServiceManifest {
+    /// Load a manifest from a YAML or JSON file.
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        let mut manifest: ServiceManifest = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest: {}", path.display()))?;
+
+        if manifest.service.is_empty() {
+            manifest.service = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string();
+        }
+
+        Ok(manifest)
+    }
+
+    /// Ask a daemon for its method listing.
+    ///
+    /// Starts the daemon if it isn't running.
+    pub fn from_daemon(service: &str) -> Result<Self> {
+        let response = fgp_daemon::client::call_auto_start(
+            service,
+            "methods",
+            Value::Object(Default::default()),
+        )
+        .with_context(|| format!("Failed to list methods of {}", service))?;
+
+        if !response.ok {
+            let error = response.error.map(|e| e.message).unwrap_or_default();
+            anyhow::bail!("Failed to list methods of {}: {}", service, error);
+        }
+
+        // The listing is either the methods themselves or {"methods": [...]}
+        let mut listing = response.result.unwrap_or(Value::Null);
+        if let Some(methods) = listing.get_mut("methods") {
+            listing = methods.take();
+        }
+        let methods = serde_json::from_value(listing)
+            .with_context(|| format!("Invalid method listing from {}", service))?;
+
+        Ok(Self {
+            service: service.to_string(),
+            methods,
+        })
+    }
+
+    /// Find a method by name, with or without the service prefix.
+    pub fn method(&self, name: &str) -> Option<&MethodSpec> {
+        let unprefixed = name
+            .strip_prefix(self.service.as_str())
+            .and_then(|rest| rest.strip_prefix('.'));
+
+        self.methods
+            .iter()
+            .find(|m| m.name == name || Some(m.name.as_str()) == unprefixed)
+    }
+}
+
+/// Manifests for a set of services.
+#[derive(Debug, Clone, Default)]
+pub struct Manifests {
+    services: IndexMap<String, ServiceManifest>,
+}
+
+impl Manifests {
+    /// Create an empty set of manifests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a manifest, replacing any for the same service.
+    pub fn add(&mut self, manifest: ServiceManifest) {
+        self.services.insert(manifest.service.clone(), manifest);
+    }
+
+    /// Add a manifest (builder style).
+    pub fn with(mut self, manifest: ServiceManifest) -> Self {
+        self.add(manifest);
+        self
+    }
+
+    /// Load every `.yaml`, `.yml` and `.json` manifest in a directory.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut manifests = Self::new();
+
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read manifest directory: {}", dir.display()))?;
+        let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+
+        for path in paths {
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            if matches!(extension, "yaml" | "yml" | "json") {
+                manifests.add(ServiceManifest::load_file(&path)?);
+            }
+        }
+
+        Ok(manifests)
+    }
+
+    /// Ask the daemons of a workflow's services for any manifests not
+    /// already loaded.
+    ///
+    /// Services whose daemon can't be reached are left without a manifest
+    /// and logged.
+    pub fn fetch_missing(&mut self, workflow: &Workflow) {
+        let services: indexmap::IndexSet<String> = workflow
+            .steps
+            .iter()
+            .filter(|s| !s.service.is_empty() && !is_template(&s.service))
+            .map(|s| s.service.clone())
+            .collect();
+
+        for service in services {
+            if self.services.contains_key(&service) {
+                continue;
+            }
+            match ServiceManifest::from_daemon(&service) {
+                Ok(manifest) => self.add(manifest),
+                Err(e) => tracing::warn!(service = %service, error = %e, "No method listing"),
+            }
+        }
+    }
+
+    /// Get the manifest of a service.
+    pub fn get(&self, service: &str) -> Option<&ServiceManifest> {
+        self.services.get(service)
+    }
+
+    /// Check a workflow's service steps against the manifests.
+    ///
+    /// Steps with templated service or method names are skipped, as are
+    /// templated param values.
+    pub(crate) fn check(&self, workflow: &Workflow) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, step) in workflow.steps.iter().enumerate() {
+            if step.is_transform()
+                || step.is_workflow()
+                || is_template(&step.service)
+                || is_template(&step.method)
+            {
+                continue;
+            }
+
+            let Some(manifest) = self.get(&step.service) else {
+                diagnostics.push(
+                    Diagnostic::new(
+                        format!("steps.{}.service", i),
+                        format!("Step {}: no manifest for service '{}'", i, step.service),
+                    )
+                    .severity(Severity::Warning),
+                );
+                continue;
+            };
+
+            match manifest.method(&step.method) {
+                Some(method) => check_params(i, step, method, &mut diagnostics),
+                None => {
+                    let mut message = format!(
+                        "Step {} calls unknown method '{}' of service '{}'",
+                        i, step.method, step.service
+                    );
+                    if let Some(suggestion) = closest(manifest, &step.method) {
+                        message.push_str(&format!(" (did you mean '{}'?)", suggestion));
+                    }
+                    diagnostics.push(Diagnostic::new(format!("steps.{}.method", i), message));
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Check a step's params against a method's declared params.
+fn check_params(index: usize, step: &Step, method: &MethodSpec, diagnostics: &mut Vec<Diagnostic>) {
+    for spec in &method.params {
+        if spec.required && spec.default.is_none() && !step.params.contains_key(&spec.name) {
+            diagnostics.push(Diagnostic::new(
+                format!("steps.{}.params", index),
+                format!(
+                    "Step {} is missing required param '{}' of {}",
+                    index, spec.name, step.method
+                ),
+            ));
+        }
+    }
+
+    for (name, param) in &step.params {
+        let path = format!("steps.{}.params.{}", index, name);
+        let Some(spec) = method.params.iter().find(|p| &p.name == name) else {
+            diagnostics.push(
+                Diagnostic::new(
+                    path,
+                    format!(
+                        "Step {} passes unknown param '{}' to {}",
+                        index, name, step.method
+                    ),
+                )
+                .severity(Severity::Warning),
+            );
+            continue;
+        };
+
+        if let Param::Literal(value) = param {
+            if !matches_type(value, &spec.param_type) {
+                diagnostics.push(Diagnostic::new(
+                    path,
+                    format!(
+                        "Step {} param '{}' should be {}, got {}",
+                        index,
+                        name,
+                        spec.param_type,
+                        type_name(value)
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Whether a value has a manifest type; unknown types accept anything.
+fn matches_type(value: &Value, param_type: &str) -> bool {
+    match param_type.to_ascii_lowercase().as_str() {
+        "string" | "str" => value.is_string(),
+        "integer" | "int" => value.is_i64() || value.is_u64(),
+        "number" | "float" => value.is_number(),
+        "boolean" | "bool" => value.is_boolean(),
+        "array" | "list" => value.is_array(),
+        "object" | "map" => value.is_object(),
+        _ => true,
+    }
+}
+
+/// JSON type name of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// The manifest method closest to a misspelled name, if any is close.
+fn closest<'a>(manifest: &'a ServiceManifest, name: &str) -> Option<&'a str> {
+    manifest
+        .methods
+        .iter()
+        .map(|m| {
+            let qualified = if m.name.contains('.') {
+                m.name.clone()
+            } else {
+                format!("{}.{}", manifest.service, m.name)
+            };
+            (edit_distance(&qualified, name), m.name.as_str())
+        })
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(current)
+            };
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gmail() -> ServiceManifest {
+        serde_yaml::from_str(
+            r#"
+service: gmail
+methods:
+  - name: gmail.inbox
+    params:
+      - name: limit
+        type: integer
+  - name: send
+    params:
+      - name: to
+        type: string
+        required: true
+      - name: cc
+        param_type: array
+"#,
+        )
+        .unwrap()
+    }
+
+    fn check(step: crate::StepBuilder) -> Vec<String> {
+        let workflow = Workflow::new("w").add(step).build();
+        Manifests::new()
+            .with(gmail())
+            .check(&workflow)
+            .into_iter()
+            .map(|d| format!("{}: {}", d.severity, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_method_lookup() {
+        let manifest = gmail();
+        assert!(manifest.method("gmail.inbox").is_some());
+        assert!(manifest.method("gmail.send").is_some());
+        assert!(manifest.method("send").is_some());
+        assert!(manifest.method("gmail.archive").is_none());
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        assert_eq!(
+            check(Step::call("gmail", "gmail.inobx")),
+            ["error: Step 0 calls unknown method 'gmail.inobx' of service 'gmail' (did you mean 'gmail.inbox'?)"]
+        );
+        assert_eq!(
+            check(Step::call("calendar", "calendar.today")),
+            ["warning: Step 0: no manifest for service 'calendar'"]
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        assert_eq!(
+            check(Step::call("gmail", "gmail.send").with_param("to", "a@b.c")),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            check(
+                Step::call("gmail", "gmail.send")
+                    .with_param("cc", "x")
+                    .with_param("bcc", "y")
+            ),
+            [
+                "error: Step 0 is missing required param 'to' of gmail.send",
+                "error: Step 0 param 'cc' should be array, got string",
+                "warning: Step 0 passes unknown param 'bcc' to gmail.send",
+            ]
+        );
+        // Templated values are only known at runtime
+        assert_eq!(
+            check(Step::call("gmail", "gmail.inbox").with_param("limit", "{{ n }}")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = crate::testing::TempDir::new("manifests");
+        std::fs::write(
+            dir.join("browser.json"),
+            r#"{"methods": [{"name": "browser.open", "params": [{"name": "url", "type": "string", "required": true}]}]}"#,
+        )
+        .unwrap();
+
+        let manifests = Manifests::load_dir(&*dir).unwrap();
+        let browser = manifests.get("browser").unwrap();
+        assert!(browser.method("browser.open").unwrap().params[0].required);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("gmail.inbox", "gmail.inobx"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
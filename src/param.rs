@@ -0,0 +1,302 @@
+//! Step parameter values.
+
+use crate::step::is_template;
+use crate::workflow::TemplateMode;
+use indexmap::IndexMap;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+/// Marker key for templates in serialized params.
+pub(crate) const TEMPLATE_KEY: &str = "__template__";
+
+/// Marker key for literal values in serialized params.
+pub(crate) const RAW_KEY: &str = "__raw__";
+
+/// A step parameter value.
+///
+/// Objects and arrays are only represented as [`Param::Object`] and
+/// [`Param::Array`] when they contain a template; otherwise the whole value
+/// is a [`Param::Literal`].
+///
+/// Serialized, templates are bare `"{{ ... }}"` strings and literals that
+/// would be mistaken for templates are wrapped as `{"__raw__": ...}`, so a
+/// param round-trips unchanged. When deserializing, `{"__template__": "..."}`
+/// is also accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    /// Value passed through unchanged.
+    Literal(Value),
+
+    /// Handlebars template rendered against the context.
+    Template(String),
+
+    /// Object with templates in some of its fields.
+    Object(IndexMap<String, Param>),
+
+    /// Array with templates in some of its items.
+    Array(Vec<Param>),
+}
+
+impl Param {
+    /// Create a template param.
+    pub fn template(template: &str) -> Self {
+        Param::Template(template.to_string())
+    }
+
+    /// Create a literal param that is never rendered.
+    pub fn literal<V: Into<Value>>(value: V) -> Self {
+        Param::Literal(value.into())
+    }
+
+    /// Interpret a value in the serialized param format.
+    ///
+    /// Like [`From<Value>`], but single-key `{"__template__": "..."}` and
+    /// `{"__raw__": ...}` objects are read as templates and literals.
+    pub fn parse(value: Value) -> Self {
+        Param::parse_with(value, TemplateMode::Auto)
+    }
+
+    /// Interpret a value in the serialized param format under a template
+    /// mode.
+    ///
+    /// With [`TemplateMode::Explicit`], bare strings are literal and only
+    /// `{"__template__": "..."}` objects are templates.
+    pub fn parse_with(value: Value, mode: TemplateMode) -> Self {
+        match value {
+            Value::Object(mut map) if map.len() == 1 => {
+                if let Some(Value::String(template)) = map.get(TEMPLATE_KEY) {
+                    return Param::Template(template.clone());
+                }
+                if let Some(raw) = map.remove(RAW_KEY) {
+                    return Param::Literal(raw);
+                }
+                object(
+                    map.into_iter()
+                        .map(|(k, v)| (k, Param::parse_with(v, mode))),
+                )
+            }
+            Value::Object(map) => object(
+                map.into_iter()
+                    .map(|(k, v)| (k, Param::parse_with(v, mode))),
+            ),
+            Value::Array(items) => array(items.into_iter().map(|v| Param::parse_with(v, mode))),
+            Value::String(s) if mode.is_auto() && is_template(&s) => Param::Template(s),
+            other => Param::Literal(other),
+        }
+    }
+
+    /// Convert a value given to a builder under a workflow's template mode.
+    ///
+    /// Auto mode detects templates as [`From<Value>`] does; explicit mode
+    /// reads the value like [`Param::parse_with`], so only
+    /// `{"__template__": "..."}` objects are templates.
+    pub(crate) fn from_builder(value: Value, mode: TemplateMode) -> Self {
+        match mode {
+            TemplateMode::Auto => Param::from(value),
+            TemplateMode::Explicit => Param::parse_with(value, mode),
+        }
+    }
+
+    /// Whether this param contains no templates.
+    pub fn is_literal(&self) -> bool {
+        matches!(self, Param::Literal(_))
+    }
+
+    /// The literal value, if this param contains no templates.
+    fn into_literal(self) -> Option<Value> {
+        match self {
+            Param::Literal(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for Param {
+    /// Strings containing `{{ }}` become templates; everything else is literal.
+    fn from(value: Value) -> Self {
+        match value {
+            Value::String(s) if is_template(&s) => Param::Template(s),
+            Value::Object(map) => object(map.into_iter().map(|(k, v)| (k, Param::from(v)))),
+            Value::Array(items) => array(items.into_iter().map(Param::from)),
+            other => Param::Literal(other),
+        }
+    }
+}
+
+/// Build an object param, collapsing it to a literal if it has no templates.
+fn object(fields: impl Iterator<Item = (String, Param)>) -> Param {
+    let fields: IndexMap<String, Param> = fields.collect();
+
+    if fields.values().all(Param::is_literal) {
+        let map = fields
+            .into_iter()
+            .filter_map(|(k, v)| v.into_literal().map(|v| (k, v)))
+            .collect();
+        Param::Literal(Value::Object(map))
+    } else {
+        Param::Object(fields)
+    }
+}
+
+/// Build an array param, collapsing it to a literal if it has no templates.
+fn array(items: impl Iterator<Item = Param>) -> Param {
+    let items: Vec<Param> = items.collect();
+
+    if items.iter().all(Param::is_literal) {
+        let values = items.into_iter().filter_map(Param::into_literal).collect();
+        Param::Literal(Value::Array(values))
+    } else {
+        Param::Array(items)
+    }
+}
+
+/// Whether a literal would be read back as something else if serialized bare.
+fn needs_raw_marker(value: &Value) -> bool {
+    match value {
+        Value::String(s) => is_template(s),
+        Value::Object(map) => {
+            (map.len() == 1 && (map.contains_key(TEMPLATE_KEY) || map.contains_key(RAW_KEY)))
+                || map.values().any(needs_raw_marker)
+        }
+        Value::Array(items) => items.iter().any(needs_raw_marker),
+        _ => false,
+    }
+}
+
+impl Serialize for Param {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        match self {
+            Param::Literal(value) if needs_raw_marker(value) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(RAW_KEY, value)?;
+                map.end()
+            }
+            Param::Literal(value) => value.serialize(serializer),
+            Param::Template(template) if is_template(template) => {
+                serializer.serialize_str(template)
+            }
+            Param::Template(template) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(TEMPLATE_KEY, template)?;
+                map.end()
+            }
+            Param::Object(fields) => serializer.collect_map(fields),
+            Param::Array(items) => serializer.collect_seq(items),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Param {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(Param::parse)
+    }
+}
+
+impl JsonSchema for Param {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "Param".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Any value. Strings containing `{{ }}` are Handlebars templates; \
+                {\"__raw__\": value} passes a value through unrendered and \
+                {\"__template__\": \"...\"} marks a template explicitly \
+                (`!raw` and `!template` tags in YAML)."
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_value_detects_templates() {
+        assert_eq!(Param::from(json!("{{ x }}")), Param::template("{{ x }}"));
+        assert_eq!(Param::from(json!(5)), Param::literal(5));
+        assert_eq!(
+            Param::from(json!({"a": 1, "b": [2]})),
+            Param::literal(json!({"a": 1, "b": [2]}))
+        );
+        assert_eq!(
+            Param::from(json!({"a": 1, "b": "{{ x }}"})),
+            Param::Object(IndexMap::from([
+                ("a".to_string(), Param::literal(1)),
+                ("b".to_string(), Param::template("{{ x }}")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_marker_key_not_hijacked_by_from_value() {
+        let value = json!({"__template__": "not a template"});
+        assert_eq!(Param::from(value.clone()), Param::Literal(value));
+    }
+
+    #[test]
+    fn test_parse_markers() {
+        assert_eq!(
+            Param::parse(json!({"__template__": "Hi {{ name }}"})),
+            Param::template("Hi {{ name }}")
+        );
+        assert_eq!(
+            Param::parse(json!({"__raw__": "{{ literal }}"})),
+            Param::literal("{{ literal }}")
+        );
+        // Only a single-key object is a marker
+        assert_eq!(
+            Param::parse(json!({"__template__": "x", "other": 1})),
+            Param::literal(json!({"__template__": "x", "other": 1}))
+        );
+    }
+
+    #[test]
+    fn test_parse_explicit_mode() {
+        assert_eq!(
+            Param::parse_with(json!({"a": "{{ x }}"}), TemplateMode::Explicit),
+            Param::literal(json!({"a": "{{ x }}"}))
+        );
+        assert_eq!(
+            Param::parse_with(json!({"__template__": "{{ x }}"}), TemplateMode::Explicit),
+            Param::template("{{ x }}")
+        );
+    }
+
+    #[test]
+    fn test_serialize_canonical_form() {
+        assert_eq!(
+            serde_json::to_value(Param::template("{{ x }}")).unwrap(),
+            json!("{{ x }}")
+        );
+        assert_eq!(
+            serde_json::to_value(Param::literal("{{ x }}")).unwrap(),
+            json!({"__raw__": "{{ x }}"})
+        );
+        assert_eq!(
+            serde_json::to_value(Param::literal(json!({"__template__": "x"}))).unwrap(),
+            json!({"__raw__": {"__template__": "x"}})
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let params = vec![
+            Param::template("{{ x }}"),
+            Param::template("no braces"),
+            Param::literal("{{ x }}"),
+            Param::literal(json!({"__raw__": 1})),
+            Param::from(json!({"a": ["{{ x }}", 1], "b": {"c": "lit"}})),
+        ];
+
+        for param in params {
+            let yaml = serde_yaml::to_string(&param).unwrap();
+            let back: Param = serde_yaml::from_str(&yaml).unwrap();
+            assert_eq!(back, param, "round trip through {}", yaml);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Finding workflows by name.
+//!
+//! A [`WorkflowRegistry`] loads every workflow file (YAML, JSON or TOML) in
+//! a set of directories and indexes them by name, so callers don't need
+//! paths:
+//!
+//! ```rust,no_run
+//! use fgp_workflow::registry::WorkflowRegistry;
+//!
+//! let mut registry = WorkflowRegistry::new()
+//!     .dir("workflows")
+//!     .user_dir()
+//!     .load()?;
+//!
+//! for problem in registry.problems() {
+//!     eprintln!("{}", problem);
+//! }
+//! for workflow in registry.iter() {
+//!     println!("{}: {}", workflow.name, workflow.description.as_deref().unwrap_or(""));
+//! }
+//!
+//! let digest = registry.get("morning-digest");
+//!
+//! // Later: pick up added, removed and edited files
+//! registry.refresh()?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Directories are searched in the order they were added, and within a
+//! directory in file name order. When two files declare the same name the
+//! first one wins and the other is reported as a [`Problem::Duplicate`].
+//! Files that another workflow in the directories includes are fragments,
+//! and are skipped rather than reported when they don't load on their own.
+
+use crate::format::load_file_tracking;
+use crate::watch::{stamp, Stamp};
+use crate::{Context, Format, Workflow};
+use anyhow::{Context as _, Result};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Workflows loaded from directories, by name.
+#[derive(Debug, Default)]
+pub struct WorkflowRegistry {
+    /// Directories to scan, and whether they may be missing
+    dirs: Vec<(PathBuf, bool)>,
+
+    workflows: IndexMap<String, Workflow>,
+
+    problems: Vec<Problem>,
+
+    /// Files read by the last load, with their state at the time
+    files: Vec<(PathBuf, Option<Stamp>)>,
+}
+
+/// A workflow file the registry could not use.
+#[derive(Debug)]
+pub enum Problem {
+    /// The file failed to load.
+    Invalid {
+        /// Workflow file
+        path: PathBuf,
+
+        /// Why it failed
+        error: anyhow::Error,
+    },
+
+    /// The file declares a name an earlier file already has.
+    Duplicate {
+        /// Workflow name
+        name: String,
+
+        /// File that was ignored
+        path: PathBuf,
+
+        /// File the name resolves to
+        first: PathBuf,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Load errors already name the file
+            Problem::Invalid { error, .. } => write!(f, "{:#}", error),
+            Problem::Duplicate { name, path, first } => write!(
+                f,
+                "{}: duplicate workflow '{}' (already defined in {})",
+                path.display(),
+                name,
+                first.display()
+            ),
+        }
+    }
+}
+
+impl Problem {
+    /// The file the problem is in.
+    pub fn path(&self) -> &Path {
+        match self {
+            Problem::Invalid { path, .. } | Problem::Duplicate { path, .. } => path,
+        }
+    }
+}
+
+impl WorkflowRegistry {
+    /// Create a registry with no directories.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a directory to scan. It must exist when the registry loads.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dirs.push((dir.into(), false));
+        self
+    }
+
+    /// Add the user's workflow directory, `~/.fgp/workflows`, if it exists.
+    pub fn user_dir(mut self) -> Self {
+        if let Some(dir) = Self::user_workflows() {
+            self.dirs.push((dir, true));
+        }
+        self
+    }
+
+    /// The user's workflow directory, `~/.fgp/workflows`.
+    pub fn user_workflows() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME")?;
+        Some(PathBuf::from(home).join(".fgp").join("workflows"))
+    }
+
+    /// Scan the directories and load their workflows.
+    ///
+    /// Fails only if a directory can't be read; files that don't load are
+    /// recorded as [`problems`](Self::problems).
+    pub fn load(mut self) -> Result<Self> {
+        self.reload()?;
+        Ok(self)
+    }
+
+    /// Scan the directories again and replace the loaded workflows.
+    pub fn reload(&mut self) -> Result<()> {
+        let mut workflows: IndexMap<String, Workflow> = IndexMap::new();
+        let mut problems = Vec::new();
+        let mut read = Vec::new();
+        let mut included = HashSet::new();
+
+        let mut loaded = Vec::new();
+        for path in self.candidates()? {
+            let format = Format::from_path(&path).expect("candidates have a known format");
+            let mut files = Vec::new();
+            let result = load_file_tracking(&path, format, &mut files);
+            included.extend(
+                files
+                    .iter()
+                    .filter(|file| **file != path)
+                    .map(|file| canonical(file)),
+            );
+            read.push(path.clone());
+            read.extend(files);
+            loaded.push((path, result));
+        }
+
+        for (path, result) in loaded {
+            match result {
+                // Fragments only make sense inside the workflows that include them
+                Err(_) if included.contains(&canonical(&path)) => {}
+                Err(error) => problems.push(Problem::Invalid { path, error }),
+                Ok(workflow) => match workflows.get(&workflow.name) {
+                    Some(first) => problems.push(Problem::Duplicate {
+                        name: workflow.name.clone(),
+                        path,
+                        first: first.source.clone().unwrap_or_default(),
+                    }),
+                    None => {
+                        workflows.insert(workflow.name.clone(), workflow);
+                    }
+                },
+            }
+        }
+
+        read.sort();
+        read.dedup();
+        self.files = read
+            .into_iter()
+            .map(|file| {
+                let stamp = stamp(&file);
+                (file, stamp)
+            })
+            .collect();
+        self.workflows = workflows;
+        self.problems = problems;
+        Ok(())
+    }
+
+    /// Reload if a workflow file or one of its includes was added, removed
+    /// or changed since the last load. Returns whether it reloaded.
+    pub fn refresh(&mut self) -> Result<bool> {
+        let mut current = self.candidates()?;
+        current.extend(self.files.iter().map(|(file, _)| file.clone()));
+        current.sort();
+        current.dedup();
+
+        let unchanged = current.len() == self.files.len()
+            && current
+                .iter()
+                .zip(&self.files)
+                .all(|(file, (known, before))| file == known && stamp(file) == *before);
+        if unchanged {
+            return Ok(false);
+        }
+
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Workflow files in the directories, in search order.
+    fn candidates(&self) -> Result<Vec<PathBuf>> {
+        let mut candidates = Vec::new();
+
+        for (dir, optional) in &self.dirs {
+            let entries = match std::fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) if *optional && !dir.exists() => continue,
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to read workflow directory: {}", dir.display())
+                    })
+                }
+            };
+
+            let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+            paths.sort();
+            candidates.extend(
+                paths
+                    .into_iter()
+                    .filter(|path| path.is_file() && Format::from_path(path).is_some()),
+            );
+        }
+
+        Ok(candidates)
+    }
+
+    /// Find a workflow by name.
+    pub fn get(&self, name: &str) -> Option<&Workflow> {
+        self.workflows.get(name)
+    }
+
+    /// Loaded workflows, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Workflow> {
+        let mut workflows: Vec<&Workflow> = self.workflows.values().collect();
+        workflows.sort_by(|a, b| a.name.cmp(&b.name));
+        workflows.into_iter()
+    }
+
+    /// Number of loaded workflows.
+    pub fn len(&self) -> usize {
+        self.workflows.len()
+    }
+
+    /// Whether no workflows are loaded.
+    pub fn is_empty(&self) -> bool {
+        self.workflows.is_empty()
+    }
+
+    /// Files that failed to load or repeat a name, from the last load.
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    /// Register every loaded workflow with a context, so `workflow:` steps
+    /// can name them. Workflows the context already has are kept.
+    pub fn register(&self, mut ctx: Context) -> Context {
+        for (name, workflow) in &self.workflows {
+            if ctx.workflow(name).is_none() {
+                ctx = ctx.with_workflow(name, workflow.clone());
+            }
+        }
+        ctx
+    }
+}
+
+/// A path in a form that compares equal however it was reached.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_load_and_lookup() {
+        let dir = TempDir::new("registry");
+        let extra = dir.join("extra");
+        std::fs::create_dir_all(&extra).unwrap();
+
+        write(
+            &dir.join("b.yaml"),
+            "name: triage\ndescription: Sort the inbox\nsteps:\n  - set: 1\n",
+        );
+        write(&dir.join("a.yaml"), "name: digest\nsteps:\n  - set: 2\n");
+        write(&dir.join("broken.yaml"), "name: broken\nsteps: 3\n");
+        write(&dir.join("README.md"), "# Workflows");
+        write(
+            &extra.join("c.json"),
+            r#"{"name": "triage", "steps": [{"set": 3}]}"#,
+        );
+
+        let registry = WorkflowRegistry::new()
+            .dir(&*dir)
+            .dir(&extra)
+            .load()
+            .unwrap();
+
+        let names: Vec<&str> = registry.iter().map(|w| w.name.as_str()).collect();
+        assert_eq!(names, vec!["digest", "triage"]);
+        assert_eq!(
+            registry.get("triage").unwrap().description.as_deref(),
+            Some("Sort the inbox")
+        );
+        assert_eq!(
+            registry.get("triage").unwrap().source,
+            Some(dir.join("b.yaml"))
+        );
+        assert!(registry.get("broken").is_none());
+
+        let problems: Vec<String> = registry.problems().iter().map(|p| p.to_string()).collect();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with(&format!("{}:", dir.join("broken.yaml").display())));
+        assert_eq!(
+            problems[1],
+            format!(
+                "{}: duplicate workflow 'triage' (already defined in {})",
+                extra.join("c.json").display(),
+                dir.join("b.yaml").display()
+            )
+        );
+
+        let ctx = registry.register(Context::new());
+        assert!(ctx.workflow("digest").is_some());
+    }
+
+    #[test]
+    fn test_missing_dirs() {
+        let scratch = TempDir::new("registry");
+        let dir = scratch.join("nowhere");
+
+        assert!(WorkflowRegistry::new().dir(&*dir).load().is_err());
+
+        let mut optional = WorkflowRegistry::new();
+        optional.dirs.push((dir, true));
+        assert!(optional.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_refresh() {
+        let dir = TempDir::new("registry");
+        write(
+            &dir.join("digest.yaml"),
+            "name: digest\nsteps:\n  - include: common.yaml\n",
+        );
+        write(&dir.join("common.yaml"), "steps:\n  - set: 1\n");
+
+        let mut registry = WorkflowRegistry::new().dir(&*dir).load().unwrap();
+        assert_eq!(registry.len(), 1);
+        assert!(registry.problems().is_empty()); // common.yaml is only included
+        assert!(!registry.refresh().unwrap());
+
+        // An edited include reloads the workflow that uses it
+        write(&dir.join("common.yaml"), "steps:\n  - set: 1\n  - set: 2\n");
+        assert!(registry.refresh().unwrap());
+        assert_eq!(registry.get("digest").unwrap().steps.len(), 2);
+
+        write(
+            &dir.join("triage.yaml"),
+            "name: triage\nsteps:\n  - set: 3\n",
+        );
+        assert!(registry.refresh().unwrap());
+        assert!(registry.get("triage").is_some());
+
+        std::fs::remove_file(dir.join("triage.yaml")).unwrap();
+        assert!(registry.refresh().unwrap());
+        assert!(registry.get("triage").is_none());
+        assert!(!registry.refresh().unwrap());
+
+        // A file without a name that nothing includes is still a mistake
+        write(&dir.join("stray.yaml"), "steps:\n  - set: 4\n");
+        assert!(registry.refresh().unwrap());
+        let problems = registry.problems();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path(), dir.join("stray.yaml"));
+    }
+}
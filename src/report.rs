@@ -0,0 +1,701 @@
+//! Run reports.
+//!
+//! A [`Report`] is a versioned JSON document of one run: the workflow, its
+//! inputs, and either the full [`ExecutionResult`] or how it failed. Reports
+//! can be shipped to dashboards, saved, and loaded back to compare two runs
+//! or replay one:
+//!
+//! ```rust,no_run
+//! use fgp_workflow::report::Report;
+//! use fgp_workflow::Context;
+//!
+//! let workflow = fgp_workflow::load_file("digest.yaml")?;
+//! let outcome = fgp_workflow::execute(&workflow);
+//! Report::new(&workflow, outcome.as_ref()).save("digest.report.json")?;
+//!
+//! let before = Report::load("digest.report.json")?;
+//! let after = Report::from_execution(&workflow, before.replay(&workflow, Context::new())?)
+//!     .inputs(before.inputs.clone());
+//! for change in before.diff(&after) {
+//!     println!("{}", change);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The `version` field is [`REPORT_VERSION`]; it changes only when a field
+//! is removed or changes meaning, and reports from newer versions are
+//! refused.
+//!
+//! A report also renders as JUnit XML ([`Report::to_junit`]), one test case
+//! per step, so CI shows smoke-test workflows like tests, and as a Markdown
+//! summary ([`Report::to_markdown`]) for pull requests and chat.
+
+use crate::history::RunStatus;
+use crate::watch::StepChange;
+use crate::{Context, ExecutionResult, Step, StepFailure, StepResult, Workflow};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Version of the report format written by this crate.
+pub const REPORT_VERSION: u32 = 1;
+
+/// JSON report of one run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// Report format version
+    pub version: u32,
+
+    /// Workflow name
+    pub workflow: String,
+
+    /// How the run ended
+    pub status: RunStatus,
+
+    /// When the report was made
+    pub created_at: DateTime<Local>,
+
+    /// Whether this is a plan: params were rendered but no service called
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+
+    /// Inputs the run was given
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub inputs: IndexMap<String, Value>,
+
+    /// Result of a successful run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<ExecutionResult>,
+
+    /// How a failed run failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<Failure>,
+}
+
+/// How a run failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    /// Error message
+    pub error: String,
+
+    /// Index of the failed step, if a step failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+
+    /// The failed step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<Step>,
+
+    /// Results of the steps before it
+    #[serde(default)]
+    pub step_results: Vec<StepResult>,
+
+    /// How the workflow run by the failed step failed, if it failed at a step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child: Option<Box<StepFailure>>,
+}
+
+impl Report {
+    /// Report a run's outcome.
+    pub fn new(workflow: &Workflow, outcome: Result<&ExecutionResult, &anyhow::Error>) -> Self {
+        let mut report = Self::empty(workflow);
+
+        match outcome {
+            Ok(result) => report.execution = Some(result.clone()),
+            Err(error) => {
+                let failure = error.downcast_ref::<StepFailure>();
+                report.status = RunStatus::Failed;
+                report.failure = Some(Failure {
+                    error: format!("{:#}", error),
+                    index: failure.map(|f| f.index),
+                    step: failure.map(|f| f.step.clone()),
+                    step_results: failure.map(|f| f.step_results.clone()).unwrap_or_default(),
+                    child: failure.and_then(|f| f.child.clone()),
+                });
+            }
+        }
+
+        report
+    }
+
+    /// Report a successful run.
+    pub fn from_execution(workflow: &Workflow, result: ExecutionResult) -> Self {
+        let mut report = Self::empty(workflow);
+        report.execution = Some(result);
+        report
+    }
+
+    /// A successful report with no results yet.
+    fn empty(workflow: &Workflow) -> Self {
+        Self {
+            version: REPORT_VERSION,
+            workflow: workflow.name.clone(),
+            status: RunStatus::Succeeded,
+            created_at: Local::now(),
+            dry_run: false,
+            inputs: IndexMap::new(),
+            execution: None,
+            failure: None,
+        }
+    }
+
+    /// Record the inputs the run was given.
+    pub fn inputs<I: IntoIterator<Item = (String, Value)>>(mut self, inputs: I) -> Self {
+        self.inputs = inputs.into_iter().collect();
+        self
+    }
+
+    /// Mark the report as one of a [`dry_run`](crate::dry_run).
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Results of the steps that completed.
+    pub fn steps(&self) -> &[StepResult] {
+        match (&self.execution, &self.failure) {
+            (Some(execution), _) => &execution.step_results,
+            (None, Some(failure)) => &failure.step_results,
+            (None, None) => &[],
+        }
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("reports serialize")
+    }
+
+    /// Read a report from JSON, refusing reports from newer versions.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json).context("Failed to parse report")?;
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .context("Not a workflow report: no version")?;
+        if version > u64::from(REPORT_VERSION) {
+            anyhow::bail!(
+                "Report version {} is newer than supported version {}",
+                version,
+                REPORT_VERSION
+            );
+        }
+
+        serde_json::from_value(value).context("Failed to parse report")
+    }
+
+    /// Write the report to a JSON file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json())
+            .with_context(|| format!("Failed to write report: {}", path.display()))
+    }
+
+    /// Read a report from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read report: {}", path.display()))?;
+        Self::from_json(&content).with_context(|| format!("Invalid report: {}", path.display()))
+    }
+
+    /// Differences in step params and results from this run to `other`.
+    pub fn diff(&self, other: &Report) -> Vec<StepChange> {
+        crate::watch::diff(self.steps(), other.steps())
+    }
+
+    /// JUnit XML of the run: a test suite named after the workflow with a
+    /// test case per step (steps of sub-workflows numbered `parent.child`).
+    ///
+    /// The failed step's test case carries the error; a run that failed
+    /// before any step (a missing input) gets a single `workflow` case.
+    /// When the failure is inside a sub-workflow, only the innermost step
+    /// counts as a failure, and the workflow steps around it name it.
+    pub fn to_junit(&self) -> String {
+        let cases = self.cases();
+        let failures = cases.iter().filter(|case| case.error.is_some()).count();
+        let seconds = self.total_ms() / 1000.0;
+
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(&format!(
+            "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            cases.len(),
+            failures,
+            seconds
+        ));
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"0\" time=\"{:.3}\" timestamp=\"{}\">\n",
+            xml_escape(&self.workflow),
+            cases.len(),
+            failures,
+            seconds,
+            self.created_at.format("%Y-%m-%dT%H:%M:%S")
+        ));
+
+        for case in &cases {
+            let name = xml_escape(&case.name());
+            let classname = xml_escape(&self.workflow);
+            let time = case.duration_ms.unwrap_or(0.0) / 1000.0;
+            match (case.error, &case.failed_at) {
+                (None, None) => out.push_str(&format!(
+                    "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"/>\n",
+                    name, classname, time
+                )),
+                (None, Some(failed_at)) => {
+                    out.push_str(&format!(
+                        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+                        name, classname, time
+                    ));
+                    out.push_str(&format!(
+                        "      <system-out>Failed at step {}</system-out>\n",
+                        failed_at
+                    ));
+                    out.push_str("    </testcase>\n");
+                }
+                (Some(error), _) => {
+                    out.push_str(&format!(
+                        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+                        name, classname, time
+                    ));
+                    out.push_str(&format!(
+                        "      <failure message=\"{}\">{}</failure>\n",
+                        xml_escape(error),
+                        xml_escape(error)
+                    ));
+                    out.push_str("    </testcase>\n");
+                }
+            }
+        }
+
+        out.push_str("  </testsuite>\n</testsuites>\n");
+        out
+    }
+
+    /// Markdown summary of the run: a heading with the outcome, a table of
+    /// steps, services and durations, and the error of a failed run.
+    pub fn to_markdown(&self) -> String {
+        let cases = self.cases();
+        let mut out = format!(
+            "### {}: {} ({} step(s), {:.1} ms)\n\n",
+            markdown_escape(&self.workflow),
+            self.status,
+            cases.len(),
+            self.total_ms()
+        );
+
+        out.push_str("| Step | Runs | Service | Duration | Outcome |\n");
+        out.push_str("|---:|---|---|---:|---|\n");
+        for case in &cases {
+            let service = case
+                .step
+                .map(|step| step.service.as_str())
+                .filter(|service| !service.is_empty())
+                .unwrap_or("-");
+            let duration = case
+                .duration_ms
+                .map(|ms| format!("{:.1} ms", ms))
+                .unwrap_or_else(|| "-".to_string());
+            let outcome = if case.error.is_some() || case.failed_at.is_some() {
+                "failed"
+            } else {
+                "succeeded"
+            };
+
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} |\n",
+                case.number,
+                markdown_escape(&case.label),
+                markdown_escape(service),
+                duration,
+                outcome
+            ));
+        }
+
+        if let Some(ref failure) = self.failure {
+            out.push_str(&format!("\n```text\n{}\n```\n", failure.error));
+        }
+        out
+    }
+
+    /// Run time in milliseconds: the total of a successful run, or the sum
+    /// of the completed steps of a failed one.
+    fn total_ms(&self) -> f64 {
+        match self.execution {
+            Some(ref execution) => execution.total_ms,
+            None => self
+                .steps()
+                .iter()
+                .fold(0.0, |ms, step| ms + step.duration_ms),
+        }
+    }
+
+    /// Completed steps in order, children after their parent, followed by
+    /// the failure and the steps of its failed sub-workflow.
+    fn cases(&self) -> Vec<Case<'_>> {
+        fn add<'a>(steps: &'a [StepResult], prefix: &str, cases: &mut Vec<Case<'a>>) {
+            for step in steps {
+                let number = format!("{}{}", prefix, step.index);
+                cases.push(Case {
+                    number: number.clone(),
+                    label: step.step.label(),
+                    step: Some(&step.step),
+                    duration_ms: Some(step.duration_ms),
+                    error: None,
+                    failed_at: None,
+                });
+                add(&step.children, &format!("{}.", number), cases);
+            }
+        }
+
+        /// The failed step of a sub-workflow and, in turn, its own. Returns
+        /// the number of the innermost failed step.
+        fn add_failure<'a>(
+            failure: &'a StepFailure,
+            prefix: &str,
+            error: &'a str,
+            cases: &mut Vec<Case<'a>>,
+        ) -> String {
+            let number = format!("{}{}", prefix, failure.index);
+            let prefix = format!("{}.", number);
+            let at = cases.len();
+            cases.push(Case {
+                number: number.clone(),
+                label: failure.step.label(),
+                step: Some(&failure.step),
+                duration_ms: None,
+                error: Some(error),
+                failed_at: None,
+            });
+            match failure.child {
+                Some(ref child) => {
+                    add(&child.step_results, &prefix, cases);
+                    let innermost = add_failure(child, &prefix, error, cases);
+                    cases[at].fail_at(&innermost);
+                    innermost
+                }
+                None => number,
+            }
+        }
+
+        let mut cases = Vec::new();
+        add(self.steps(), "", &mut cases);
+
+        if let Some(ref failure) = self.failure {
+            cases.push(Case {
+                number: failure
+                    .index
+                    .map(|index| index.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                label: failure
+                    .step
+                    .as_ref()
+                    .map(Step::label)
+                    .unwrap_or_else(|| "workflow".to_string()),
+                step: failure.step.as_ref(),
+                duration_ms: None,
+                error: Some(&failure.error),
+                failed_at: None,
+            });
+            if let (Some(index), Some(child)) = (failure.index, &failure.child) {
+                let at = cases.len() - 1;
+                let prefix = format!("{}.", index);
+                add(&child.step_results, &prefix, &mut cases);
+                let innermost = add_failure(child, &prefix, &failure.error, &mut cases);
+                cases[at].fail_at(&innermost);
+            }
+        }
+        cases
+    }
+
+    /// Run the workflow again with the report's inputs set on `ctx`.
+    pub fn replay(&self, workflow: &Workflow, mut ctx: Context) -> Result<ExecutionResult> {
+        if workflow.name != self.workflow {
+            anyhow::bail!(
+                "Report is for workflow '{}', not '{}'",
+                self.workflow,
+                workflow.name
+            );
+        }
+
+        for (name, value) in &self.inputs {
+            ctx.set(name, value.clone());
+        }
+        crate::execute_with_context(workflow, ctx)
+    }
+}
+
+/// A step as a test case.
+struct Case<'a> {
+    /// Step number, `parent.child` for steps of sub-workflows
+    number: String,
+    label: String,
+    step: Option<&'a Step>,
+    duration_ms: Option<f64>,
+    error: Option<&'a str>,
+
+    /// For a workflow step, the number of the step inside it that failed
+    failed_at: Option<String>,
+}
+
+impl Case<'_> {
+    /// Leave the error to the step inside this one that failed.
+    fn fail_at(&mut self, number: &str) {
+        self.error = None;
+        self.failed_at = Some(number.to_string());
+    }
+
+    /// Test case name: `[1] gmail.inbox`, with the step id if it has one.
+    fn name(&self) -> String {
+        match self.step.and_then(|step| step.id.as_deref()) {
+            Some(id) => format!("[{}] {} ({})", self.number, self.label, id),
+            None => format!("[{}] {}", self.number, self.label),
+        }
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Escape characters that would break a Markdown table cell.
+fn markdown_escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execute_with_context;
+    use crate::secrets::MemorySecretProvider;
+
+    fn workflow() -> Workflow {
+        Workflow::new("greet")
+            .input("who")
+            .add(Step::transform("Hello {{ who }}").output("greeting"))
+            .add(Step::transform("{{ greeting }}!").id("shout"))
+            .build()
+    }
+
+    fn run(who: &str) -> Report {
+        let workflow = workflow();
+        let mut ctx = Context::new();
+        ctx.set("who", Value::from(who));
+        let outcome = execute_with_context(&workflow, ctx);
+
+        Report::new(&workflow, outcome.as_ref()).inputs([("who".to_string(), Value::from(who))])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let report = run("Ada");
+        let json = report.to_json();
+        let value: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["version"], REPORT_VERSION);
+        assert_eq!(value["status"], "succeeded");
+        assert_eq!(value["inputs"]["who"], "Ada");
+        assert_eq!(value["execution"]["result"], "Hello Ada!");
+        assert_eq!(
+            value["execution"]["context"]["variables"]["greeting"],
+            "Hello Ada"
+        );
+        assert_eq!(value["execution"]["step_results"][1]["step"]["id"], "shout");
+
+        let loaded = Report::from_json(&json).unwrap();
+        assert_eq!(loaded.workflow, "greet");
+        assert_eq!(loaded.steps().len(), 2);
+        assert_eq!(loaded.steps()[1].result, "Hello Ada!");
+        let execution = loaded.execution.as_ref().unwrap();
+        assert_eq!(
+            execution.context.get("greeting"),
+            Some(&Value::from("Hello Ada"))
+        );
+    }
+
+    #[test]
+    fn test_failed_run() {
+        let workflow = Workflow::new("broken")
+            .add(Step::transform(1))
+            .add(Step::workflow("missing"))
+            .build();
+        let err = crate::execute(&workflow).unwrap_err();
+
+        let report = Report::from_json(&Report::new(&workflow, Err(&err)).to_json()).unwrap();
+        let failure = report.failure.as_ref().unwrap();
+
+        assert_eq!(report.status, RunStatus::Failed);
+        assert!(report.execution.is_none());
+        assert_eq!(failure.index, Some(1));
+        assert_eq!(failure.step.as_ref().unwrap().label(), "workflow missing");
+        assert_eq!(
+            failure.error,
+            "Step 1 (workflow missing) failed: Workflow 'missing' is not registered"
+        );
+        assert_eq!(report.steps()[0].result, 1);
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut value = serde_json::to_value(run("Ada")).unwrap();
+
+        value["version"] = Value::from(REPORT_VERSION + 1);
+        let err = Report::from_json(&value.to_string()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Report version {} is newer than supported version {}",
+                REPORT_VERSION + 1,
+                REPORT_VERSION
+            )
+        );
+
+        let err = Report::from_json("{\"workflow\": \"greet\"}").unwrap_err();
+        assert_eq!(err.to_string(), "Not a workflow report: no version");
+    }
+
+    #[test]
+    fn test_diff_and_replay() {
+        let before = run("Ada");
+        let after = run("Grace");
+
+        let changes: Vec<String> = before.diff(&after).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                "~ [0] transform result: \"Hello Ada\" -> \"Hello Grace\"",
+                "~ [1] transform result: \"Hello Ada!\" -> \"Hello Grace!\"",
+            ]
+        );
+
+        let replayed = before.replay(&workflow(), Context::new()).unwrap();
+        let replay = Report::from_execution(&workflow(), replayed).inputs(before.inputs.clone());
+        assert!(before.diff(&replay).is_empty());
+
+        let other = Workflow::new("other").add(Step::transform(1)).build();
+        assert!(before.replay(&other, Context::new()).is_err());
+    }
+
+    /// A successful report with fixed durations and time.
+    fn timed_report() -> Report {
+        let workflow = Workflow::new("digest")
+            .add(Step::transform(1).id("count"))
+            .add(Step::workflow("child"))
+            .build();
+        let child = Workflow::new("child").add(Step::transform(2)).build();
+        let outcome = execute_with_context(&workflow, Context::new().with_workflow("child", child));
+
+        let mut report = Report::new(&workflow, outcome.as_ref());
+        report.created_at = "2026-10-18T08:00:00+00:00".parse().unwrap();
+        let execution = report.execution.as_mut().unwrap();
+        execution.total_ms = 812.4;
+        execution.step_results[0].duration_ms = 12.0;
+        execution.step_results[1].duration_ms = 800.0;
+        execution.step_results[1].children[0].duration_ms = 0.5;
+        report
+    }
+
+    #[test]
+    fn test_junit() {
+        assert_eq!(
+            timed_report().to_junit(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="3" failures="0" time="0.812">
+  <testsuite name="digest" tests="3" failures="0" errors="0" skipped="0" time="0.812" timestamp="2026-10-18T08:00:00">
+    <testcase name="[0] transform (count)" classname="digest" time="0.012"/>
+    <testcase name="[1] workflow child" classname="digest" time="0.800"/>
+    <testcase name="[1.0] transform" classname="digest" time="0.001"/>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn test_junit_failure() {
+        let workflow = Workflow::new("broken")
+            .add(Step::transform(1))
+            .add(Step::workflow("<missing>"))
+            .build();
+        let err = crate::execute(&workflow).unwrap_err();
+        let junit = Report::new(&workflow, Err(&err)).to_junit();
+
+        assert!(junit.contains("<testsuites tests=\"2\" failures=\"1\""));
+        assert!(junit.contains(
+            r#"    <testcase name="[1] workflow &lt;missing&gt;" classname="broken" time="0.000">
+      <failure message="Step 1 (workflow &lt;missing&gt;) failed: Workflow &apos;&lt;missing&gt;&apos; is not registered">"#
+        ));
+
+        // Failing before any step runs
+        let workflow = Workflow::new("needs-input")
+            .input("who")
+            .add(Step::transform(1))
+            .build();
+        let err = crate::execute(&workflow).unwrap_err();
+        let junit = Report::new(&workflow, Err(&err)).to_junit();
+        assert!(junit.contains("<testcase name=\"[-] workflow\""));
+    }
+
+    #[test]
+    fn test_junit_sub_workflow_failure() {
+        let child = Workflow::new("child")
+            .add(Step::transform(2))
+            .add(Step::transform("{{ secrets.missing }}"))
+            .build();
+        let workflow = Workflow::new("parent").add(Step::workflow("child")).build();
+        let ctx = Context::new()
+            .with_secrets(crate::secrets::MemorySecretProvider::new())
+            .with_workflow("child", child);
+        let err = execute_with_context(&workflow, ctx).unwrap_err();
+
+        let report = Report::from_json(&Report::new(&workflow, Err(&err)).to_json()).unwrap();
+        let junit = report.to_junit();
+
+        assert!(junit.contains("<testsuites tests=\"3\" failures=\"1\""));
+        assert!(junit.contains(
+            r#"    <testcase name="[0] workflow child" classname="parent" time="0.000">
+      <system-out>Failed at step 0.1</system-out>"#
+        ));
+        assert_eq!(junit.matches("<failure ").count(), 1);
+        let names: Vec<&str> = junit
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("<testcase name=\""))
+            .map(|line| &line[..line.find('"').unwrap()])
+            .collect();
+        assert_eq!(
+            names,
+            ["[0] workflow child", "[0.0] transform", "[0.1] transform"]
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            timed_report().to_markdown(),
+            "### digest: succeeded (3 step(s), 812.4 ms)
+
+| Step | Runs | Service | Duration | Outcome |
+|---:|---|---|---:|---|
+| 0 | transform | - | 12.0 ms | succeeded |
+| 1 | workflow child | - | 800.0 ms | succeeded |
+| 1.0 | transform | - | 0.5 ms | succeeded |
+"
+        );
+
+        // Fails resolving params, before calling the service
+        let workflow = Workflow::new("send")
+            .add(Step::call("gmail", "gmail.send").with_param("token", "{{ secrets.missing }}"))
+            .build();
+        let ctx = Context::new().with_secrets(MemorySecretProvider::new());
+        let err = execute_with_context(&workflow, ctx).unwrap_err();
+        let markdown = Report::new(&workflow, Err(&err)).to_markdown();
+        assert!(markdown.starts_with("### send: failed (1 step(s), 0.0 ms)"));
+        assert!(markdown.contains("| 0 | gmail.send | gmail | - | failed |"));
+        assert!(markdown.ends_with(&format!("```text\n{:#}\n```\n", err)));
+    }
+}
@@ -0,0 +1,816 @@
+//! Running workflows on cron schedules.
+//!
+//! A workflow declares when it runs with a `schedule:` field, either a cron
+//! expression or a mapping with an overlap policy and inputs:
+//!
+//! ```yaml
+//! name: morning-digest
+//! schedule: "0 8 * * 1-5"
+//! steps:
+//!   - service: gmail
+//!     method: gmail.inbox
+//!
+//! # or
+//! schedule:
+//!   cron: "*/15 * * * *"
+//!   overlap: queue
+//!   inputs:
+//!     account: work
+//! ```
+//!
+//! Cron expressions have five fields (minute, hour, day of month, month,
+//! day of week), an optional leading seconds field, or are one of `@hourly`,
+//! `@daily`, `@weekly`, `@monthly` and `@yearly`. They are read in local
+//! time.
+//!
+//! Schedules can also live in a separate manifest, with paths relative to
+//! it. An entry without `cron` uses the workflow's own schedule:
+//!
+//! ```yaml
+//! schedules:
+//!   - workflow: workflows/triage.yaml
+//!     cron: "0 * * * *"
+//!     overlap: skip
+//!   - workflow: workflows/digest.yaml
+//! ```
+//!
+//! A [`Scheduler`] runs the workflows in-process, each run on its own
+//! thread, and keeps a [`RunSummary`] of every run:
+//!
+//! ```rust,no_run
+//! use fgp_workflow::schedule::Scheduler;
+//! use std::sync::atomic::AtomicBool;
+//!
+//! let mut scheduler = Scheduler::new().on_run(|run| println!("{}", run));
+//! scheduler.add(fgp_workflow::load_file("workflows/digest.yaml")?)?;
+//! scheduler.load_manifest("schedules.yaml")?;
+//!
+//! scheduler.run_until(&AtomicBool::new(false));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use crate::history::{History, RunStatus};
+use crate::{Context, StepFailure, Workflow};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local};
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Number of run summaries a [`Scheduler`] keeps.
+pub const MAX_RUNS: usize = 1000;
+
+/// Longest the scheduler sleeps before checking for a stop request.
+const MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// When a workflow runs on its own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(from = "ScheduleDef")]
+pub struct Schedule {
+    /// Cron expression
+    pub cron: String,
+
+    /// What to do when a run is due while the previous one is still going
+    #[serde(default, skip_serializing_if = "Overlap::is_skip")]
+    pub overlap: Overlap,
+
+    /// Inputs each scheduled run gets
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub inputs: IndexMap<String, Value>,
+}
+
+/// A schedule as written: a cron expression or a full mapping.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ScheduleDef {
+    Cron(String),
+    Full {
+        cron: String,
+        #[serde(default)]
+        overlap: Overlap,
+        #[serde(default)]
+        inputs: IndexMap<String, Value>,
+    },
+}
+
+impl From<ScheduleDef> for Schedule {
+    fn from(def: ScheduleDef) -> Self {
+        match def {
+            ScheduleDef::Cron(cron) => Schedule::new(&cron),
+            ScheduleDef::Full {
+                cron,
+                overlap,
+                inputs,
+            } => Schedule {
+                cron,
+                overlap,
+                inputs,
+            },
+        }
+    }
+}
+
+/// What to do when a run is due while the previous run is still going.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Overlap {
+    /// Skip the new run.
+    #[default]
+    Skip,
+
+    /// Start the new run once the previous one finishes.
+    Queue,
+
+    /// Start the new run alongside the previous one.
+    Allow,
+}
+
+impl Overlap {
+    /// Whether this is the default policy.
+    pub fn is_skip(&self) -> bool {
+        *self == Overlap::Skip
+    }
+}
+
+impl Schedule {
+    /// Create a schedule from a cron expression, skipping overlapping runs.
+    pub fn new(cron: &str) -> Self {
+        Self {
+            cron: cron.to_string(),
+            overlap: Overlap::Skip,
+            inputs: IndexMap::new(),
+        }
+    }
+
+    /// Set the overlap policy.
+    pub fn overlap(mut self, overlap: Overlap) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Set an input for scheduled runs.
+    pub fn input<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.inputs.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Parse the cron expression.
+    pub(crate) fn parse(&self) -> Result<cron::Schedule> {
+        let expression = self.cron.trim();
+        // Standard five-field expressions get a seconds field
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {}", expression)
+        } else {
+            expression.to_string()
+        };
+
+        cron::Schedule::from_str(&expression)
+            .map_err(|e| anyhow::anyhow!("Invalid cron expression '{}': {}", self.cron, e))
+    }
+
+    /// The first time after `after` that the schedule fires.
+    pub fn next_after(&self, after: DateTime<Local>) -> Result<Option<DateTime<Local>>> {
+        Ok(self.parse()?.after(&after).next())
+    }
+}
+
+/// Summary of one scheduled run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    /// Workflow name
+    pub workflow: String,
+
+    /// When the run was due
+    pub scheduled_at: DateTime<Local>,
+
+    /// When the run started (when it was skipped, for skipped runs)
+    pub started_at: DateTime<Local>,
+
+    /// How the run ended
+    pub status: RunStatus,
+
+    /// Run time in milliseconds
+    pub duration_ms: f64,
+
+    /// Number of steps that completed
+    pub steps: usize,
+
+    /// Index of the step that failed
+    pub failed_step: Option<usize>,
+
+    /// Error message of a failed run
+    pub error: Option<String>,
+
+    /// Final result of a successful run
+    pub result: Value,
+}
+
+/// One line per run:
+///
+/// ```text
+/// 2026-10-19 08:00:00 morning-digest: succeeded (3 steps, 812.4 ms)
+/// ```
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {}",
+            self.scheduled_at.format("%Y-%m-%d %H:%M:%S"),
+            self.workflow,
+            self.status
+        )?;
+        match self.status {
+            RunStatus::Skipped => f.write_str(" (previous run still going)"),
+            _ => {
+                write!(f, " ({} steps, {:.1} ms)", self.steps, self.duration_ms)?;
+                match self.error {
+                    Some(ref error) => write!(f, ": {}", error),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Builds the context each run starts with.
+type ContextFn = dyn Fn() -> Context + Send + Sync;
+
+/// Called with each run summary.
+type RunFn = dyn Fn(&RunSummary) + Send + Sync;
+
+/// Runs workflows on their schedules.
+pub struct Scheduler {
+    jobs: Vec<Job>,
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// State shared with run threads.
+struct Shared {
+    settings: RwLock<Settings>,
+    runs: Mutex<VecDeque<RunSummary>>,
+}
+
+/// How runs are started and reported. Each run uses the settings current
+/// when it starts, so they can change while other runs are in progress.
+#[derive(Clone)]
+struct Settings {
+    context: Arc<ContextFn>,
+    on_run: Option<Arc<RunFn>>,
+    history: Option<History>,
+}
+
+/// A scheduled workflow.
+struct Job {
+    workflow: Arc<Workflow>,
+    schedule: Schedule,
+    cron: cron::Schedule,
+
+    /// Next time the job is due, once planned
+    next: Option<DateTime<Local>>,
+
+    state: Arc<Mutex<JobState>>,
+}
+
+/// Runs of a job in progress.
+#[derive(Default)]
+struct JobState {
+    running: usize,
+
+    /// Due times of queued runs
+    queued: VecDeque<DateTime<Local>>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    /// Create a scheduler with no workflows.
+    pub fn new() -> Self {
+        Self {
+            jobs: Vec::new(),
+            shared: Arc::new(Shared {
+                settings: RwLock::new(Settings {
+                    context: Arc::new(Context::new),
+                    on_run: None,
+                    history: None,
+                }),
+                runs: Mutex::new(VecDeque::new()),
+            }),
+            threads: Vec::new(),
+        }
+    }
+
+    /// Build the context each run starts with (secrets, registered
+    /// workflows). The schedule's inputs are set on it.
+    pub fn context<F: Fn() -> Context + Send + Sync + 'static>(self, context: F) -> Self {
+        self.settings_mut().context = Arc::new(context);
+        self
+    }
+
+    /// Call `on_run` with the summary of each run as it finishes.
+    pub fn on_run<F: Fn(&RunSummary) + Send + Sync + 'static>(self, on_run: F) -> Self {
+        self.settings_mut().on_run = Some(Arc::new(on_run));
+        self
+    }
+
+    /// Record every run that starts in a run history.
+    pub fn history(self, history: History) -> Self {
+        self.settings_mut().history = Some(history);
+        self
+    }
+
+    fn settings_mut(&self) -> RwLockWriteGuard<'_, Settings> {
+        self.shared
+            .settings
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Schedule a workflow by its `schedule:` field.
+    pub fn add(&mut self, workflow: Workflow) -> Result<()> {
+        let schedule = workflow
+            .schedule
+            .clone()
+            .with_context(|| format!("Workflow '{}' has no schedule", workflow.name))?;
+        self.add_with(workflow, schedule)
+    }
+
+    /// Schedule a workflow on the given schedule, ignoring its own.
+    pub fn add_with(&mut self, workflow: Workflow, schedule: Schedule) -> Result<()> {
+        let cron = schedule
+            .parse()
+            .with_context(|| format!("Invalid schedule for workflow '{}'", workflow.name))?;
+
+        self.jobs.push(Job {
+            workflow: Arc::new(workflow),
+            schedule,
+            cron,
+            next: None,
+            state: Arc::default(),
+        });
+        Ok(())
+    }
+
+    /// Schedule the workflows listed in a schedule manifest.
+    pub fn load_manifest(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read schedule manifest: {}", path.display()))?;
+        let manifest: Manifest = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse schedule manifest: {}", path.display()))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for entry in manifest.schedules {
+            let workflow = crate::load_file(base.join(&entry.workflow))?;
+            let schedule = match entry.cron {
+                Some(cron) => Schedule {
+                    cron,
+                    overlap: entry.overlap.unwrap_or_default(),
+                    inputs: entry.inputs,
+                },
+                None => {
+                    let mut schedule = workflow.schedule.clone().with_context(|| {
+                        format!(
+                            "{} has no schedule and the manifest gives no cron",
+                            entry.workflow
+                        )
+                    })?;
+                    if let Some(overlap) = entry.overlap {
+                        schedule.overlap = overlap;
+                    }
+                    schedule.inputs.extend(entry.inputs);
+                    schedule
+                }
+            };
+            self.add_with(workflow, schedule)?;
+        }
+
+        Ok(())
+    }
+
+    /// Scheduled workflows and the next time each is due.
+    pub fn upcoming(&self) -> Vec<(&Workflow, Option<DateTime<Local>>)> {
+        let now = Local::now();
+        self.jobs
+            .iter()
+            .map(|job| {
+                let next = job.next.or_else(|| job.cron.after(&now).next());
+                (job.workflow.as_ref(), next)
+            })
+            .collect()
+    }
+
+    /// Start the runs that are due at `now`.
+    ///
+    /// Each job fires at most once per call, however many times it was due
+    /// since the last call. Jobs are planned on their first call and fire
+    /// from the next due time after it. Returns the number of runs started.
+    pub fn run_due(&mut self, now: DateTime<Local>) -> usize {
+        self.threads.retain(|thread| !thread.is_finished());
+
+        let mut started = 0;
+        for i in 0..self.jobs.len() {
+            let job = &mut self.jobs[i];
+            let Some(due) = job.next else {
+                job.next = job.cron.after(&now).next();
+                continue;
+            };
+            if due > now {
+                continue;
+            }
+            job.next = job.cron.after(&now).next();
+
+            if let Some(thread) = self.fire(i, due) {
+                self.threads.push(thread);
+                started += 1;
+            }
+        }
+        started
+    }
+
+    /// Run jobs as they become due until `stop` is set, then wait for the
+    /// runs in progress.
+    pub fn run_until(&mut self, stop: &AtomicBool) {
+        while !stop.load(Ordering::Relaxed) {
+            let now = Local::now();
+            self.run_due(now);
+
+            let sleep = self
+                .jobs
+                .iter()
+                .filter_map(|job| job.next)
+                .min()
+                .and_then(|next| (next - now).to_std().ok())
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            std::thread::sleep(sleep);
+        }
+
+        self.wait();
+    }
+
+    /// Wait for the runs in progress to finish.
+    pub fn wait(&mut self) {
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+
+    /// Summaries of recent runs, oldest first (at most [`MAX_RUNS`]).
+    pub fn runs(&self) -> Vec<RunSummary> {
+        let runs = self.shared.runs.lock().unwrap_or_else(|e| e.into_inner());
+        runs.iter().cloned().collect()
+    }
+
+    /// Start a due run of a job, applying its overlap policy.
+    fn fire(&self, index: usize, due: DateTime<Local>) -> Option<JoinHandle<()>> {
+        let job = &self.jobs[index];
+        {
+            let mut state = job.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.running > 0 {
+                match job.schedule.overlap {
+                    Overlap::Skip => {
+                        tracing::info!(workflow = %job.workflow.name, "Skipping run, previous run still going");
+                        self.shared.record(RunSummary {
+                            workflow: job.workflow.name.clone(),
+                            scheduled_at: due,
+                            started_at: Local::now(),
+                            status: RunStatus::Skipped,
+                            duration_ms: 0.0,
+                            steps: 0,
+                            failed_step: None,
+                            error: None,
+                            result: Value::Null,
+                        });
+                        return None;
+                    }
+                    Overlap::Queue => {
+                        tracing::info!(workflow = %job.workflow.name, "Queueing run, previous run still going");
+                        state.queued.push_back(due);
+                        return None;
+                    }
+                    Overlap::Allow => {}
+                }
+            }
+            state.running += 1;
+        }
+
+        let workflow = Arc::clone(&job.workflow);
+        let inputs = job.schedule.inputs.clone();
+        let state = Arc::clone(&job.state);
+        let shared = Arc::clone(&self.shared);
+
+        Some(std::thread::spawn(move || {
+            let mut due = due;
+            loop {
+                let summary = shared.run(&workflow, &inputs, due);
+                shared.record(summary);
+
+                // Queued runs go next, on this thread
+                let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                match state.queued.pop_front() {
+                    Some(next) => due = next,
+                    None => {
+                        state.running -= 1;
+                        return;
+                    }
+                }
+            }
+        }))
+    }
+}
+
+impl Shared {
+    fn settings(&self) -> Settings {
+        self.settings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Run a workflow once and summarize the run.
+    fn run(
+        &self,
+        workflow: &Workflow,
+        inputs: &IndexMap<String, Value>,
+        due: DateTime<Local>,
+    ) -> RunSummary {
+        let settings = self.settings();
+        let mut ctx = (settings.context)();
+        for (name, value) in inputs {
+            ctx.set(name, value.clone());
+        }
+
+        tracing::info!(workflow = %workflow.name, "Starting scheduled run");
+        let started_at = Local::now();
+        let start = std::time::Instant::now();
+        let outcome = crate::execute_with_context(workflow, ctx);
+        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        if let Some(ref history) = settings.history {
+            history.record_or_warn(workflow, inputs, started_at, outcome.as_ref());
+        }
+
+        let mut summary = RunSummary {
+            workflow: workflow.name.clone(),
+            scheduled_at: due,
+            started_at,
+            status: RunStatus::Succeeded,
+            duration_ms,
+            steps: 0,
+            failed_step: None,
+            error: None,
+            result: Value::Null,
+        };
+
+        match outcome {
+            Ok(result) => {
+                summary.steps = result.step_results.len();
+                summary.result = result.result;
+            }
+            Err(error) => {
+                tracing::warn!(workflow = %workflow.name, error = %format!("{:#}", error), "Scheduled run failed");
+                if let Some(failure) = error.downcast_ref::<StepFailure>() {
+                    summary.steps = failure.step_results.len();
+                    summary.failed_step = Some(failure.index);
+                }
+                summary.status = RunStatus::Failed;
+                summary.error = Some(format!("{:#}", error));
+            }
+        }
+
+        summary
+    }
+
+    /// Keep a run summary and report it.
+    fn record(&self, summary: RunSummary) {
+        if let Some(ref on_run) = self.settings().on_run {
+            on_run(&summary);
+        }
+
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        if runs.len() == MAX_RUNS {
+            runs.pop_front();
+        }
+        runs.push_back(summary);
+    }
+}
+
+/// A schedule manifest file.
+#[derive(Deserialize)]
+struct Manifest {
+    schedules: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    workflow: String,
+    cron: Option<String>,
+    overlap: Option<Overlap>,
+    #[serde(default)]
+    inputs: IndexMap<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_yaml, Step};
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 3, 2, hour, minute, 0)
+            .single()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_schedule_field() {
+        let workflow = parse_yaml(
+            r#"
+name: digest
+schedule: "0 8 * * *"
+steps:
+  - set: 1
+"#,
+        )
+        .unwrap();
+        assert_eq!(workflow.schedule, Some(Schedule::new("0 8 * * *")));
+
+        let workflow = parse_yaml(
+            r#"
+name: triage
+schedule:
+  cron: "*/15 * * * *"
+  overlap: queue
+  inputs:
+    account: work
+steps:
+  - set: 1
+"#,
+        )
+        .unwrap();
+        let schedule = workflow.schedule.clone().unwrap();
+        assert_eq!(
+            schedule,
+            Schedule::new("*/15 * * * *")
+                .overlap(Overlap::Queue)
+                .input("account", "work")
+        );
+        assert_eq!(schedule.next_after(at(8, 7)).unwrap(), Some(at(8, 15)));
+
+        let yaml = workflow.to_yaml().unwrap();
+        assert_eq!(parse_yaml(&yaml).unwrap().schedule, Some(schedule));
+    }
+
+    #[test]
+    fn test_invalid_schedule() {
+        let err = parse_yaml(
+            r#"
+name: digest
+schedule: "0 25 * * *"
+steps:
+  - set: 1
+"#,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Invalid cron expression '0 25 * * *'"));
+    }
+
+    #[test]
+    fn test_run_due() {
+        let workflow = Workflow::new("hourly")
+            .add(Step::transform("{{ account }}"))
+            .build();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .add_with(workflow, Schedule::new("@hourly").input("account", "work"))
+            .unwrap();
+
+        // The first call plans, later calls fire when due
+        assert_eq!(scheduler.run_due(at(8, 30)), 0);
+        assert_eq!(scheduler.run_due(at(8, 59)), 0);
+        assert_eq!(scheduler.run_due(at(11, 0)), 1);
+        assert_eq!(scheduler.run_due(at(11, 1)), 0);
+        scheduler.wait();
+
+        let runs = scheduler.runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Succeeded);
+        assert_eq!(runs[0].scheduled_at, at(9, 0));
+        assert_eq!(runs[0].result, Value::from("work"));
+        assert_eq!(scheduler.upcoming()[0].1, Some(at(12, 0)));
+    }
+
+    #[test]
+    fn test_overlap_policies() {
+        let workflow = Workflow::new("busy").add(Step::transform(1)).build();
+        let mut scheduler = Scheduler::new();
+        for overlap in [Overlap::Skip, Overlap::Queue] {
+            scheduler
+                .add_with(workflow.clone(), Schedule::new("@hourly").overlap(overlap))
+                .unwrap();
+        }
+        for job in &scheduler.jobs {
+            job.state.lock().unwrap().running = 1;
+        }
+
+        scheduler.run_due(at(8, 30));
+        assert_eq!(scheduler.run_due(at(9, 0)), 0);
+
+        let runs = scheduler.runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Skipped);
+        assert_eq!(
+            scheduler.jobs[1].state.lock().unwrap().queued,
+            vec![at(9, 0)]
+        );
+    }
+
+    #[test]
+    fn test_configure_while_running() {
+        let workflow = Workflow::new("hourly").add(Step::transform(1)).build();
+        let mut scheduler = Scheduler::new();
+        scheduler
+            .add_with(workflow, Schedule::new("@hourly").overlap(Overlap::Allow))
+            .unwrap();
+        scheduler.run_due(at(8, 30));
+        assert_eq!(scheduler.run_due(at(9, 0)), 1);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        let mut scheduler = scheduler.on_run(move |run| {
+            let _ = sender.lock().unwrap().send(run.scheduled_at);
+        });
+        assert_eq!(scheduler.run_due(at(10, 0)), 1);
+        scheduler.wait();
+
+        assert!(receiver.try_iter().any(|due| due == at(10, 0)));
+        assert_eq!(scheduler.runs().len(), 2);
+    }
+
+    #[test]
+    fn test_failed_run_summary() {
+        let workflow = Workflow::new("broken")
+            .add(Step::transform(1))
+            .add(Step::workflow("missing"))
+            .build();
+        let shared = Scheduler::new().shared;
+
+        let summary = shared.run(&workflow, &IndexMap::new(), at(8, 0));
+
+        assert_eq!(summary.status, RunStatus::Failed);
+        assert_eq!(summary.steps, 1);
+        assert_eq!(summary.failed_step, Some(1));
+        assert_eq!(
+            summary.error.as_deref(),
+            Some("Step 1 (workflow missing) failed: Workflow 'missing' is not registered")
+        );
+        assert!(summary.to_string().contains(" broken: failed (1 steps, "));
+    }
+
+    #[test]
+    fn test_load_manifest() {
+        let dir = crate::testing::TempDir::new("schedule");
+        std::fs::create_dir_all(dir.join("workflows")).unwrap();
+        std::fs::write(
+            dir.join("workflows/digest.yaml"),
+            "name: digest\nschedule: \"0 8 * * *\"\nsteps:\n  - set: 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("schedules.yaml"),
+            "schedules:
+  - workflow: workflows/digest.yaml
+  - workflow: workflows/digest.yaml
+    cron: \"0 * * * *\"
+    overlap: allow
+",
+        )
+        .unwrap();
+
+        let mut scheduler = Scheduler::new();
+        scheduler.load_manifest(dir.join("schedules.yaml")).unwrap();
+
+        assert_eq!(scheduler.jobs[0].schedule, Schedule::new("0 8 * * *"));
+        assert_eq!(
+            scheduler.jobs[1].schedule,
+            Schedule::new("0 * * * *").overlap(Overlap::Allow)
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! JSON Schema for workflow files.
+//!
+//! The schema is generated from the [`Workflow`] and [`Step`](crate::Step)
+//! types, so it always matches what the loaders accept. A copy is published
+//! at `schema/workflow.schema.json` for editors; with the YAML language
+//! server, point a workflow file at it with:
+//!
+//! ```yaml
+//! # yaml-language-server: $schema=../schema/workflow.schema.json
+//! ```
+
+use crate::Workflow;
+use serde_json::{json, Value};
+
+/// JSON Schema (draft 2020-12) for workflow files.
+///
+/// Besides the fields of [`Workflow`], it covers the file-only `import`,
+/// `fragments` and `include` entries and the `set` alias of `transform`.
+///
+/// ```rust
+/// let schema = fgp_workflow::schema::workflow_schema();
+/// assert_eq!(schema["title"], "Workflow");
+/// ```
+pub fn workflow_schema() -> Value {
+    let mut schema = schemars::schema_for!(Workflow).to_value();
+
+    let step_entry = json!({
+        "anyOf": [
+            {"$ref": "#/$defs/Step"},
+            {"$ref": "#/$defs/Include"}
+        ]
+    });
+
+    schema["$defs"]["Include"] = json!({
+        "description": "Steps spliced in from another file (`common.yaml`), a fragment of another \
+            file (`common.yaml#fetch-inbox`) or a fragment in scope (`fetch-inbox`).",
+        "type": "object",
+        "properties": {
+            "include": {"type": "string"}
+        },
+        "required": ["include"],
+        "additionalProperties": false
+    });
+
+    let step = &mut schema["$defs"]["Step"]["properties"];
+    let mut set = step["transform"].clone();
+    set["description"] = json!("Alias of `transform`.");
+    step["set"] = set;
+
+    let root = &mut schema["properties"];
+    root["steps"]["items"] = step_entry.clone();
+    root["import"] = json!({
+        "description": "Files whose fragments can be included by name.",
+        "anyOf": [
+            {"type": "string"},
+            {"type": "array", "items": {"type": "string"}}
+        ]
+    });
+    root["fragments"] = json!({
+        "description": "Named lists of steps that can be included.",
+        "type": "object",
+        "additionalProperties": {"type": "array", "items": step_entry}
+    });
+
+    schema
+}
+
+/// [`workflow_schema`] as pretty-printed JSON, as published in
+/// `schema/workflow.schema.json`.
+pub fn workflow_schema_json() -> String {
+    let mut json =
+        serde_json::to_string_pretty(&workflow_schema()).expect("schema serializes to JSON");
+    json.push('\n');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLISHED: &str = include_str!("../schema/workflow.schema.json");
+
+    /// The published schema must match the types.
+    ///
+    /// Run with `FGP_UPDATE_SCHEMA=1` to regenerate it.
+    #[test]
+    fn test_published_schema_is_current() {
+        let generated = workflow_schema_json();
+
+        if std::env::var_os("FGP_UPDATE_SCHEMA").is_some() {
+            let path = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/workflow.schema.json");
+            std::fs::write(path, &generated).unwrap();
+            return;
+        }
+
+        assert!(
+            generated == PUBLISHED,
+            "schema/workflow.schema.json is out of date; run the tests with FGP_UPDATE_SCHEMA=1"
+        );
+    }
+
+    #[test]
+    fn test_schema_covers_step_fields() {
+        let schema = workflow_schema();
+        let step = &schema["$defs"]["Step"]["properties"];
+
+        for field in [
+            "id",
+            "service",
+            "method",
+            "workflow",
+            "params",
+            "transform",
+            "set",
+            "output",
+            "description",
+        ] {
+            assert!(step.get(field).is_some(), "missing step field {}", field);
+        }
+        assert!(schema["properties"].get("source").is_none());
+    }
+}
@@ -0,0 +1,308 @@
+//! Secret providers and redaction.
+//!
+//! Templates can reference secrets as `{{ secrets.NAME }}`. Values are looked
+//! up through a [`SecretProvider`] when a template uses them, and every value
+//! handed out is remembered by the context's [`Redactor`] so it can be masked
+//! wherever results surface.
+
+use anyhow::{Context as _, Result};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Replacement for secret values in redacted output.
+pub const REDACTED: &str = "***";
+
+/// Source of secret values.
+pub trait SecretProvider: Send + Sync + std::fmt::Debug {
+    /// Look up a secret by name, returning `None` if it doesn't exist.
+    fn get(&self, name: &str) -> Result<Option<String>>;
+}
+
+/// Secrets read from environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecretProvider {
+    prefix: String,
+}
+
+impl EnvSecretProvider {
+    /// Read `secrets.NAME` from the environment variable `NAME`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `secrets.NAME` from the environment variable `<prefix>NAME`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+impl SecretProvider for EnvSecretProvider {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(std::env::var(format!("{}{}", self.prefix, name)).ok())
+    }
+}
+
+/// Secrets loaded from a local file.
+///
+/// `.yaml`, `.yml` and `.json` files are a mapping of names to values; any
+/// other file holds dotenv-style `NAME=value` lines.
+#[derive(Clone, Default)]
+pub struct FileSecretProvider {
+    values: HashMap<String, String>,
+}
+
+impl FileSecretProvider {
+    /// Load secrets from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read secrets file: {}", path.display()))?;
+
+        let extension = path.extension().and_then(|e| e.to_str());
+        let values = match extension {
+            Some("yaml" | "yml" | "json") => parse_mapping(&content),
+            _ => parse_dotenv(&content),
+        }
+        .with_context(|| format!("Invalid secrets file: {}", path.display()))?;
+
+        Ok(Self { values })
+    }
+}
+
+impl std::fmt::Debug for FileSecretProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileSecretProvider")
+            .field("names", &self.values.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl SecretProvider for FileSecretProvider {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.values.get(name).cloned())
+    }
+}
+
+/// Parse a YAML or JSON mapping of secret names to string values.
+fn parse_mapping(content: &str) -> Result<HashMap<String, String>> {
+    let map = match serde_yaml::from_str(content)? {
+        serde_yaml::Value::Mapping(map) => map,
+        serde_yaml::Value::Null => return Ok(HashMap::new()),
+        _ => anyhow::bail!("Expected a mapping of secret names to values"),
+    };
+
+    map.into_iter()
+        .map(|(key, value)| {
+            let key = match key {
+                serde_yaml::Value::String(key) => key,
+                other => anyhow::bail!("Secret name {:?} is not a string", other),
+            };
+            match value {
+                serde_yaml::Value::String(value) => Ok((key, value)),
+                _ => anyhow::bail!("Secret '{}' is not a string; quote its value", key),
+            }
+        })
+        .collect()
+}
+
+/// Parse `NAME=value` lines, skipping blank lines and `#` comments.
+///
+/// Lines that don't parse are reported by number only, so their content
+/// (possibly a secret) doesn't end up in logs.
+fn parse_dotenv(content: &str) -> Result<HashMap<String, String>> {
+    let mut values = HashMap::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = line
+            .split_once('=')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .with_context(|| format!("Line {} is not NAME=value", number + 1))?;
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        values.insert(name.trim().to_string(), value.to_string());
+    }
+    Ok(values)
+}
+
+/// In-memory secrets, e.g. fetched from a keyring at startup or set in tests.
+#[derive(Clone, Default)]
+pub struct MemorySecretProvider {
+    values: HashMap<String, String>,
+}
+
+impl MemorySecretProvider {
+    /// Create an empty provider.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a secret.
+    pub fn with(mut self, name: &str, value: &str) -> Self {
+        self.values.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+impl std::fmt::Debug for MemorySecretProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemorySecretProvider")
+            .field("names", &self.values.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl SecretProvider for MemorySecretProvider {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.values.get(name).cloned())
+    }
+}
+
+/// Masks secret values that have been used during execution.
+///
+/// Clones share the same set of values.
+#[derive(Clone, Default)]
+pub struct Redactor {
+    values: Arc<RwLock<BTreeSet<String>>>,
+}
+
+impl Redactor {
+    /// Create an empty redactor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember a secret value to mask.
+    pub fn add(&self, value: &str) {
+        if !value.is_empty() {
+            self.values
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(value.to_string());
+        }
+    }
+
+    /// Whether any secret values are known.
+    pub fn is_empty(&self) -> bool {
+        self.values
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+
+    /// Mask secret values in a string.
+    pub fn redact_str(&self, s: &str) -> String {
+        let values = self.values.read().unwrap_or_else(|e| e.into_inner());
+
+        // Longest first, so a secret containing another is masked whole
+        let mut sorted: Vec<&String> = values.iter().collect();
+        sorted.sort_by_key(|v| std::cmp::Reverse(v.len()));
+
+        let mut result = s.to_string();
+        for value in sorted {
+            if result.contains(value.as_str()) {
+                result = result.replace(value.as_str(), REDACTED);
+            }
+        }
+        result
+    }
+
+    /// Mask secret values in every string of a JSON value.
+    pub fn redact(&self, value: &Value) -> Value {
+        if self.is_empty() {
+            return value.clone();
+        }
+
+        match value {
+            Value::String(s) => Value::String(self.redact_str(s)),
+            Value::Array(arr) => Value::Array(arr.iter().map(|v| self.redact(v)).collect()),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (k.clone(), self.redact(v)))
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Redactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let count = self.values.read().map(|v| v.len()).unwrap_or_default();
+        f.debug_struct("Redactor").field("secrets", &count).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_provider() {
+        let provider = MemorySecretProvider::new().with("token", "s3cret");
+
+        assert_eq!(provider.get("token").unwrap(), Some("s3cret".to_string()));
+        assert_eq!(provider.get("missing").unwrap(), None);
+        assert!(!format!("{:?}", provider).contains("s3cret"));
+    }
+
+    #[test]
+    fn test_env_provider_prefix() {
+        std::env::set_var("FGP_TEST_SECRET_TOKEN", "from-env");
+        let provider = EnvSecretProvider::with_prefix("FGP_TEST_SECRET_");
+
+        assert_eq!(provider.get("TOKEN").unwrap(), Some("from-env".to_string()));
+    }
+
+    #[test]
+    fn test_parse_secrets_formats() {
+        let yaml = parse_mapping("token: abc\naccount: \"42\"\n").unwrap();
+        assert_eq!(yaml.get("token"), Some(&"abc".to_string()));
+        assert_eq!(yaml.get("account"), Some(&"42".to_string()));
+        assert!(parse_mapping("token: [abc\n").is_err());
+        assert!(parse_mapping("- abc\n").is_err());
+
+        let dotenv = parse_dotenv("# comment\nTOKEN=abc=def\n\nNAME=\"quoted\"\n").unwrap();
+        assert_eq!(dotenv.get("TOKEN"), Some(&"abc=def".to_string()));
+        assert_eq!(dotenv.get("NAME"), Some(&"quoted".to_string()));
+
+        let err = parse_dotenv("TOKEN=abc\ns3cret\n").unwrap_err();
+        assert_eq!(err.to_string(), "Line 2 is not NAME=value");
+    }
+
+    #[test]
+    fn test_file_provider_rejects_non_string_values() {
+        let dir = crate::testing::TempDir::new("secrets");
+        let path = dir.join("secrets.yaml");
+        std::fs::write(&path, "token: abc\nport: 8080\n").unwrap();
+
+        let err = FileSecretProvider::load(&path).unwrap_err();
+
+        let message = format!("{:#}", err);
+        assert!(message.contains(&path.display().to_string()));
+        assert!(message.contains("Secret 'port' is not a string"));
+    }
+
+    #[test]
+    fn test_redactor() {
+        let redactor = Redactor::new();
+        redactor.add("abc");
+        redactor.add("abcdef");
+
+        assert_eq!(redactor.redact_str("key=abcdef;abc"), "key=***;***");
+        assert_eq!(
+            redactor.redact(&serde_json::json!({"auth": ["Bearer abc", 1]})),
+            serde_json::json!({"auth": ["Bearer ***", 1]})
+        );
+    }
+}
@@ -0,0 +1,597 @@
+//! Serving workflows as an FGP daemon.
+//!
+//! [`WorkflowService`] serves the workflows of a
+//! [`WorkflowRegistry`] and answers the
+//! same protocol as any other daemon, so FGP clients can call workflows the
+//! way they call `gmail` or `browser`:
+//!
+//! | Method              | Params                       | Returns                          |
+//! |---------------------|------------------------------|----------------------------------|
+//! | `workflow.list`     |                              | Workflows, their inputs, errors  |
+//! | `workflow.run`      | `name`, `inputs`             | The run's [`Report`]             |
+//! | `workflow.validate` | `name` or `definition`       | Whether it's valid, diagnostics  |
+//! | `workflow.status`   | `name` (optional)            | The last run of each workflow    |
+//!
+//! A run that fails still answers with its report, whose `status` is
+//! `failed`. Requests fail only when they can't start a run: an unknown
+//! workflow, or missing or malformed inputs.
+//!
+//! ```rust,no_run
+//! use fgp_workflow::service::WorkflowService;
+//!
+//! let service = WorkflowService::load_dir("workflows")?;
+//! let socket = WorkflowService::default_socket().expect("a home directory");
+//! service.serve(&socket)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! The registry is refreshed before each request, so added, removed and
+//! edited files are picked up without a restart. Its workflows are
+//! registered with each other by name, so a `workflow:` step can run a
+//! sibling without a path.
+
+use crate::diagnostic::Diagnostic;
+use crate::history::{History, RunStatus};
+use crate::registry::WorkflowRegistry;
+use crate::report::Report;
+use crate::{Context, ExecutionResult, StepFailure, Workflow};
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local};
+use fgp_daemon::protocol::{MethodInfo, ParamInfo};
+use fgp_daemon::service::FgpService;
+use indexmap::IndexMap;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Name the service registers under.
+pub const SERVICE_NAME: &str = "workflow";
+
+/// Builds the context each run starts with.
+type ContextFn = dyn Fn() -> Context + Send + Sync;
+
+/// A daemon service that lists, runs and validates workflows.
+pub struct WorkflowService {
+    registry: Mutex<WorkflowRegistry>,
+
+    context: Box<ContextFn>,
+
+    history: Option<History>,
+
+    /// Last run of each workflow, by name
+    runs: Mutex<HashMap<String, LastRun>>,
+}
+
+/// What the service remembers of a workflow's latest run.
+#[derive(Debug, Clone)]
+struct LastRun {
+    /// How it ended, or `None` while it's running
+    status: Option<RunStatus>,
+    started_at: DateTime<Local>,
+    duration_ms: Option<f64>,
+    steps: usize,
+    failed_step: Option<usize>,
+    error: Option<String>,
+
+    /// Number of runs since the service started
+    count: usize,
+}
+
+impl WorkflowService {
+    /// Create a service for the workflows of a registry.
+    pub fn new(registry: WorkflowRegistry) -> Self {
+        Self {
+            registry: Mutex::new(registry),
+            context: Box::new(Context::new),
+            history: None,
+            runs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a service for the workflow files (YAML, JSON or TOML) in a
+    /// directory.
+    ///
+    /// Files that fail to load are skipped and reported by `workflow.list`.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let registry = WorkflowRegistry::new().dir(dir.as_ref()).load()?;
+        Ok(Self::new(registry))
+    }
+
+    /// Build the context each run starts with (secrets, other registered
+    /// workflows). The caller's inputs are set on it.
+    pub fn context<F: Fn() -> Context + Send + Sync + 'static>(mut self, context: F) -> Self {
+        self.context = Box::new(context);
+        self
+    }
+
+    /// Record every run in a run history.
+    pub fn history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// The socket the service listens on by default:
+    /// `~/.fgp/services/workflow/daemon.sock`.
+    pub fn default_socket() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME")?;
+        Some(
+            PathBuf::from(home)
+                .join(".fgp")
+                .join("services")
+                .join(SERVICE_NAME)
+                .join("daemon.sock"),
+        )
+    }
+
+    /// Listen on `socket` and answer requests until the server stops.
+    pub fn serve(self, socket: &Path) -> Result<()> {
+        if let Some(parent) = socket.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let socket = socket.to_string_lossy();
+        fgp_daemon::server::FgpServer::new(self, &socket)?.serve()
+    }
+
+    /// The registry, refreshed from disk.
+    fn registry(&self) -> MutexGuard<'_, WorkflowRegistry> {
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(error) = registry.refresh() {
+            tracing::warn!(error = %format!("{:#}", error), "Failed to reload workflows");
+        }
+        registry
+    }
+
+    fn list(&self) -> Value {
+        let registry = self.registry();
+        let workflows: Vec<Value> = registry
+            .iter()
+            .map(|workflow| {
+                let inputs: serde_json::Map<String, Value> = workflow
+                    .inputs
+                    .iter()
+                    .map(|(name, input)| {
+                        let mut value = json!({ "required": input.default.is_none() });
+                        if let Some(ref description) = input.description {
+                            value["description"] = Value::from(description.as_str());
+                        }
+                        if let Some(ref default) = input.default {
+                            value["default"] = default.clone();
+                        }
+                        (name.clone(), value)
+                    })
+                    .collect();
+
+                json!({
+                    "name": workflow.name,
+                    "description": workflow.description,
+                    "steps": workflow.steps.len(),
+                    "inputs": inputs,
+                    "schedule": workflow.schedule.as_ref().map(|s| s.cron.as_str()),
+                    "file": workflow.source.as_ref().map(|p| p.display().to_string()),
+                })
+            })
+            .collect();
+
+        let errors: Vec<Value> = registry
+            .problems()
+            .iter()
+            .map(|problem| {
+                json!({
+                    "file": problem.path().display().to_string(),
+                    "error": problem.to_string(),
+                })
+            })
+            .collect();
+
+        json!({ "workflows": workflows, "errors": errors })
+    }
+
+    fn run(&self, params: &HashMap<String, Value>) -> Result<Value> {
+        // The registry stays unlocked while the workflow runs
+        let (workflow, mut ctx) = {
+            let registry = self.registry();
+            let workflow = workflow(&registry, params)?.clone();
+            (workflow, registry.register((self.context)()))
+        };
+
+        let inputs: IndexMap<String, Value> = match params.get("inputs") {
+            Some(Value::Object(inputs)) => inputs.clone().into_iter().collect(),
+            Some(Value::Null) | None => IndexMap::new(),
+            Some(_) => anyhow::bail!("Param 'inputs' must be an object"),
+        };
+        for (name, value) in &inputs {
+            ctx.set(name, value.clone());
+        }
+        for (name, input) in &workflow.inputs {
+            if input.default.is_none() && ctx.get(name).is_none() {
+                anyhow::bail!("Workflow '{}' requires input '{}'", workflow.name, name);
+            }
+        }
+
+        let started_at = Local::now();
+        self.record(&workflow.name, started_at, None);
+        let outcome = crate::execute_with_context(&workflow, ctx);
+        self.record(&workflow.name, started_at, Some(outcome.as_ref()));
+
+        if let Some(ref history) = self.history {
+            history.record_or_warn(&workflow, &inputs, started_at, outcome.as_ref());
+        }
+
+        // A failed run is still a run: report it rather than fail the request
+        let report = Report::new(&workflow, outcome.as_ref()).inputs(inputs);
+        Ok(serde_json::to_value(report)?)
+    }
+
+    /// Note that a run started (`outcome` is `None`) or finished.
+    fn record(
+        &self,
+        name: &str,
+        started_at: DateTime<Local>,
+        outcome: Option<Result<&ExecutionResult, &anyhow::Error>>,
+    ) {
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        let count = runs.get(name).map_or(0, |run| run.count);
+
+        let mut run = LastRun {
+            status: None,
+            started_at,
+            duration_ms: None,
+            steps: 0,
+            failed_step: None,
+            error: None,
+            count: count + 1,
+        };
+        match outcome {
+            None => {}
+            Some(Ok(result)) => {
+                run.status = Some(RunStatus::Succeeded);
+                run.duration_ms = Some(result.total_ms);
+                run.steps = result.step_results.len();
+                run.count = count;
+            }
+            Some(Err(error)) => {
+                let failure = error.downcast_ref::<StepFailure>();
+                run.status = Some(RunStatus::Failed);
+                run.duration_ms = Some(elapsed_ms(started_at));
+                run.steps = failure.map_or(0, |f| f.step_results.len());
+                run.failed_step = failure.map(|f| f.index);
+                run.error = Some(format!("{:#}", error));
+                run.count = count;
+            }
+        }
+
+        runs.insert(name.to_string(), run);
+    }
+
+    fn validate(&self, params: &HashMap<String, Value>) -> Result<Value> {
+        let workflow = match params.get("definition") {
+            Some(Value::String(definition)) => crate::parse_yaml(definition),
+            Some(definition) => crate::json::parse_json(&definition.to_string()),
+            None => workflow(&self.registry(), params).cloned(),
+        };
+
+        // A definition that doesn't load has one error, rather than failing
+        let diagnostics = match workflow {
+            Ok(workflow) => workflow.validate(),
+            Err(error) => vec![match error.downcast_ref::<Diagnostic>() {
+                Some(diagnostic) => diagnostic.clone(),
+                None => Diagnostic::new("", format!("{:#}", error)),
+            }],
+        };
+
+        let valid = !diagnostics.iter().any(|d| d.is_error());
+        let diagnostics: Vec<Value> = diagnostics.iter().map(diagnostic_json).collect();
+        Ok(json!({ "valid": valid, "diagnostics": diagnostics }))
+    }
+
+    fn status(&self, params: &HashMap<String, Value>) -> Result<Value> {
+        let registry = self.registry();
+        let runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+
+        if params.contains_key("name") {
+            let workflow = workflow(&registry, params)?;
+            return Ok(status_json(&workflow.name, runs.get(&workflow.name)));
+        }
+
+        let statuses: Vec<Value> = registry
+            .iter()
+            .map(|workflow| status_json(&workflow.name, runs.get(&workflow.name)))
+            .collect();
+        Ok(json!({ "workflows": statuses }))
+    }
+}
+
+impl FgpService for WorkflowService {
+    fn name(&self) -> &str {
+        SERVICE_NAME
+    }
+
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn dispatch(&self, method: &str, params: HashMap<String, Value>) -> Result<Value> {
+        let method = method
+            .strip_prefix(SERVICE_NAME)
+            .and_then(|m| m.strip_prefix('.'))
+            .unwrap_or(method);
+
+        match method {
+            "list" => Ok(self.list()),
+            "run" => self.run(&params),
+            "validate" => self.validate(&params),
+            "status" => self.status(&params),
+            _ => anyhow::bail!("Unknown method: {}.{}", SERVICE_NAME, method),
+        }
+    }
+
+    fn method_list(&self) -> Vec<MethodInfo> {
+        let param = |name: &str, param_type: &str, required: bool| ParamInfo {
+            name: name.to_string(),
+            param_type: param_type.to_string(),
+            required,
+            default: None,
+        };
+        let method = |name: &str, description: &str, params| MethodInfo {
+            name: format!("{}.{}", SERVICE_NAME, name),
+            description: description.to_string(),
+            params,
+        };
+
+        vec![
+            method("list", "List the loaded workflows", vec![]),
+            method(
+                "run",
+                "Run a workflow and return its result and steps",
+                vec![
+                    param("name", "string", true),
+                    param("inputs", "object", false),
+                ],
+            ),
+            method(
+                "validate",
+                "Check a loaded workflow, or a YAML or JSON definition",
+                vec![
+                    param("name", "string", false),
+                    param("definition", "any", false),
+                ],
+            ),
+            method(
+                "status",
+                "Show the last run of a workflow, or of every workflow",
+                vec![param("name", "string", false)],
+            ),
+        ]
+    }
+}
+
+fn workflow<'a>(
+    registry: &'a WorkflowRegistry,
+    params: &HashMap<String, Value>,
+) -> Result<&'a Workflow> {
+    let name = string_param(params, "name")?;
+    registry
+        .get(name)
+        .with_context(|| format!("Unknown workflow '{}'", name))
+}
+
+fn string_param<'a>(params: &'a HashMap<String, Value>, name: &str) -> Result<&'a str> {
+    params
+        .get(name)
+        .with_context(|| format!("Missing param '{}'", name))?
+        .as_str()
+        .with_context(|| format!("Param '{}' must be a string", name))
+}
+
+fn elapsed_ms(since: DateTime<Local>) -> f64 {
+    (Local::now() - since).num_microseconds().unwrap_or(0) as f64 / 1000.0
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> Value {
+    let mut value = json!({
+        "severity": diagnostic.severity.to_string(),
+        "path": diagnostic.path,
+        "message": diagnostic.message,
+    });
+    if let Some(location) = diagnostic.location {
+        value["line"] = Value::from(location.line);
+        value["column"] = Value::from(location.column);
+    }
+    value
+}
+
+fn status_json(name: &str, run: Option<&LastRun>) -> Value {
+    let Some(run) = run else {
+        return json!({ "name": name, "status": "never_run", "runs": 0 });
+    };
+
+    json!({
+        "name": name,
+        "status": run.status.map_or(json!("running"), |status| json!(status)),
+        "runs": run.count,
+        "started_at": run.started_at.to_rfc3339(),
+        "duration_ms": run.duration_ms,
+        "steps": run.steps,
+        "failed_step": run.failed_step,
+        "error": run.error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::REPORT_VERSION;
+    use crate::testing::TempDir;
+
+    fn params(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// A service loaded from a scratch directory unique to the test.
+    fn service(test: &str) -> (WorkflowService, TempDir) {
+        let dir = TempDir::new(&format!("service-{}", test));
+        std::fs::write(
+            dir.join("greet.yaml"),
+            r#"
+name: greet
+description: Say hello
+inputs:
+  who:
+    description: Who to greet
+steps:
+  - set: "Hello {{ who }}"
+    output: greeting
+  - workflow: shout
+    params:
+      text: "{{ greeting }}"
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("shout.json"),
+            r#"{"name": "shout", "inputs": {"text": null}, "steps": [{"set": "{{ text }}!"}]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.yaml"), "name: broken\nsteps: 3\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a workflow").unwrap();
+
+        (WorkflowService::load_dir(&*dir).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_list() {
+        let (service, dir) = service("list");
+        let listing = service.dispatch("workflow.list", HashMap::new()).unwrap();
+
+        assert_eq!(listing["workflows"][0]["name"], "greet");
+        assert_eq!(listing["workflows"][0]["description"], "Say hello");
+        assert_eq!(
+            listing["workflows"][0]["inputs"]["who"],
+            json!({"required": true, "description": "Who to greet"})
+        );
+        assert_eq!(listing["workflows"][1]["name"], "shout");
+        assert_eq!(listing["errors"].as_array().unwrap().len(), 1);
+        assert!(listing["errors"][0]["file"]
+            .as_str()
+            .unwrap()
+            .ends_with("broken.yaml"));
+
+        // Files added and removed since the last request are picked up
+        std::fs::write(dir.join("broken.yaml"), "name: fixed\nsteps:\n  - set: 1\n").unwrap();
+        std::fs::remove_file(dir.join("shout.json")).unwrap();
+        let listing = service.dispatch("workflow.list", HashMap::new()).unwrap();
+        let names: Vec<&str> = listing["workflows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|w| w["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["fixed", "greet"]);
+        assert_eq!(listing["errors"], json!([]));
+    }
+
+    #[test]
+    fn test_run_and_status() {
+        let (service, dir) = service("run_and_status");
+
+        let status = service
+            .dispatch("status", params(json!({"name": "greet"})))
+            .unwrap();
+        assert_eq!(status["status"], "never_run");
+
+        let run = service
+            .dispatch(
+                "workflow.run",
+                params(json!({"name": "greet", "inputs": {"who": "Ada"}})),
+            )
+            .unwrap();
+        assert_eq!(run["version"], REPORT_VERSION);
+        assert_eq!(run["status"], "succeeded");
+        assert_eq!(run["inputs"], json!({"who": "Ada"}));
+        let execution = &run["execution"];
+        assert_eq!(execution["result"], "Hello Ada!");
+        assert_eq!(execution["step_results"][1]["step"]["workflow"], "shout");
+        assert_eq!(
+            execution["step_results"][1]["children"][0]["result"],
+            "Hello Ada!"
+        );
+
+        // A bad request is an error, and not a run
+        let err = service
+            .dispatch("workflow.run", params(json!({"name": "greet"})))
+            .unwrap_err();
+        assert!(err.to_string().contains("requires input 'who'"));
+
+        std::fs::write(
+            dir.join("leak.yaml"),
+            "name: leak\nsteps:\n  - set: 1\n  - set: \"{{ secrets.token }}\"\n",
+        )
+        .unwrap();
+        let run = service
+            .dispatch("workflow.run", params(json!({"name": "leak"})))
+            .unwrap();
+        assert_eq!(run["status"], "failed");
+        assert_eq!(run["failure"]["index"], 1);
+        assert_eq!(run["failure"]["step_results"][0]["result"], 1);
+
+        let status = service.dispatch("workflow.status", HashMap::new()).unwrap();
+        assert_eq!(status["workflows"][0]["name"], "greet");
+        assert_eq!(status["workflows"][0]["status"], "succeeded");
+        assert_eq!(status["workflows"][0]["runs"], 1);
+        assert_eq!(status["workflows"][1]["name"], "leak");
+        assert_eq!(status["workflows"][1]["status"], "failed");
+        assert_eq!(status["workflows"][1]["failed_step"], 1);
+        assert_eq!(status["workflows"][2]["status"], "never_run");
+    }
+
+    #[test]
+    fn test_validate() {
+        let (service, _dir) = service("validate");
+
+        let report = service
+            .dispatch("workflow.validate", params(json!({"name": "greet"})))
+            .unwrap();
+        assert_eq!(report["valid"], true);
+
+        let report = service
+            .dispatch(
+                "workflow.validate",
+                params(json!({"definition": "name: inline\nsteps:\n  - set: \"{{ nope }}\"\n"})),
+            )
+            .unwrap();
+        assert_eq!(report["valid"], false);
+        assert_eq!(report["diagnostics"][0]["path"], "steps.0.transform");
+
+        let report = service
+            .dispatch(
+                "workflow.validate",
+                params(json!({"definition": "name: [inline"})),
+            )
+            .unwrap();
+        assert_eq!(report["valid"], false);
+    }
+
+    #[test]
+    fn test_unknown_requests() {
+        let (service, _dir) = service("unknown_requests");
+
+        let err = service
+            .dispatch("workflow.run", params(json!({"name": "missing"})))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unknown workflow 'missing'");
+
+        let err = service
+            .dispatch("workflow.run", HashMap::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Missing param 'name'");
+
+        let err = service
+            .dispatch("workflow.delete", HashMap::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unknown method: workflow.delete");
+
+        assert_eq!(service.method_list().len(), 4);
+    }
+}
@@ -1,28 +1,49 @@
 //! Workflow step definitions.
 
+use crate::param::Param;
+use crate::workflow::TemplateMode;
+use indexmap::IndexMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
 
 /// A single step in a workflow.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Step {
-    /// Service to call (e.g., "gmail", "browser")
+    /// Step identifier, exposed to templates as `steps.<id>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// Service to call (e.g., "gmail", "browser"); may be a template
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub service: String,
 
-    /// Method to call (e.g., "gmail.inbox", "browser.open")
+    /// Method to call (e.g., "gmail.inbox", "browser.open"); may be a template
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub method: String,
 
-    /// Parameters to pass to the method
-    #[serde(default)]
-    pub params: HashMap<String, Value>,
+    /// Workflow to run instead of calling a service: a registered name or a
+    /// file path (relative to the calling workflow's file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow: Option<String>,
+
+    /// Parameters to pass to the method (or inputs of the workflow)
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub params: IndexMap<String, Param>,
+
+    /// Value to compute from the context instead of calling a daemon.
+    ///
+    /// Steps with a transform are evaluated locally: templates are resolved
+    /// against the context and the result is stored like any other step result.
+    #[serde(default, alias = "set", skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Param>,
 
-    /// Variable name to store the result (optional)
-    #[serde(default)]
-    pub output: Option<String>,
+    /// Variable name (or names) to store the result (optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Output>,
 
     /// Description for logging/debugging
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 }
 
@@ -36,12 +57,148 @@ impl Step {
     pub fn service(service: &str) -> StepBuilder {
         StepBuilder::new(service, service)
     }
+
+    /// Create a transform step that computes a value without calling a daemon.
+    ///
+    /// Strings may be templates; a template consisting of a single
+    /// `{{ variable.path }}` expression yields the referenced value as-is.
+    pub fn transform<V: Into<Value>>(value: V) -> StepBuilder {
+        let mut builder = StepBuilder::new("", "");
+        builder.transform = Some(BuilderParam::Value(value.into()));
+        builder
+    }
+
+    /// Create a step that runs another workflow.
+    ///
+    /// Params become the child workflow's inputs; its declared outputs (or
+    /// last result) become this step's result.
+    pub fn workflow(target: &str) -> StepBuilder {
+        let mut builder = StepBuilder::new("", "");
+        builder.step.workflow = Some(target.to_string());
+        builder
+    }
+
+    /// Whether this step is a transform (no daemon call).
+    pub fn is_transform(&self) -> bool {
+        self.transform.is_some()
+    }
+
+    /// Whether this step runs another workflow.
+    pub fn is_workflow(&self) -> bool {
+        self.workflow.is_some()
+    }
+
+    /// Short description of what the step runs: its method, `transform`,
+    /// or `workflow <target>`.
+    pub fn label(&self) -> String {
+        if self.is_transform() {
+            "transform".to_string()
+        } else if let Some(ref target) = self.workflow {
+            format!("workflow {}", target)
+        } else {
+            self.method.clone()
+        }
+    }
+}
+
+/// Whether a string contains template syntax.
+pub(crate) fn is_template(s: &str) -> bool {
+    s.contains("{{") && s.contains("}}")
+}
+
+/// Whether a string is a valid service or method name.
+pub(crate) fn is_valid_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Where a step's result is stored in the context.
+///
+/// ```yaml
+/// output: emails                 # whole result
+/// output:                        # parts of the result
+///   emails: messages
+///   cursor: next_page_token
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Output {
+    /// Store the whole result under one variable name.
+    Name(String),
+
+    /// Store parts of the result, mapping variable names to dotted paths
+    /// into the result (an empty path stores the whole result).
+    Bindings(IndexMap<String, String>),
+}
+
+impl Output {
+    /// Variable names this output binds.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Output::Name(name) => vec![name.as_str()],
+            Output::Bindings(bindings) => bindings.keys().map(String::as_str).collect(),
+        }
+    }
+
+    /// Extract the values to store for a step result.
+    ///
+    /// Paths that don't exist in the result bind `null`.
+    pub fn bind(&self, result: &Value) -> Vec<(String, Value)> {
+        match self {
+            Output::Name(name) => vec![(name.clone(), result.clone())],
+            Output::Bindings(bindings) => bindings
+                .iter()
+                .map(|(name, path)| {
+                    let value = crate::context::lookup_path(result, path)
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    (name.clone(), value)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&str> for Output {
+    fn from(name: &str) -> Self {
+        Output::Name(name.to_string())
+    }
 }
 
 /// Builder for creating workflow steps.
+///
+/// Values given to [`with_param`](Self::with_param),
+/// [`with_params`](Self::with_params) and [`Step::transform`] are kept as
+/// given, so a [`WorkflowBuilder`](crate::WorkflowBuilder) the builder is
+/// added to can read them under its template mode.
 #[derive(Debug, Clone)]
 pub struct StepBuilder {
+    /// The step, without its params and transform until it's built
     step: Step,
+
+    params: IndexMap<String, BuilderParam>,
+
+    transform: Option<BuilderParam>,
+}
+
+/// A param given to a [`StepBuilder`], before the template mode reads it.
+#[derive(Debug, Clone)]
+enum BuilderParam {
+    /// A value whose templates are detected by the template mode
+    Value(Value),
+
+    /// A param whose kind was given explicitly
+    Param(Param),
+}
+
+impl BuilderParam {
+    fn build(self, mode: TemplateMode) -> Param {
+        match self {
+            BuilderParam::Value(value) => Param::from_builder(value, mode),
+            BuilderParam::Param(param) => param,
+        }
+    }
 }
 
 impl StepBuilder {
@@ -49,18 +206,26 @@ impl StepBuilder {
     pub fn new(service: &str, method: &str) -> Self {
         Self {
             step: Step {
+                id: None,
                 service: service.to_string(),
                 method: method.to_string(),
-                params: HashMap::new(),
+                workflow: None,
+                params: IndexMap::new(),
+                transform: None,
                 output: None,
                 description: None,
             },
+            params: IndexMap::new(),
+            transform: None,
         }
     }
 
     /// Add a parameter.
+    ///
+    /// Strings containing `{{ }}` are treated as templates, as in YAML.
     pub fn with_param<V: Into<Value>>(mut self, key: &str, value: V) -> Self {
-        self.step.params.insert(key.to_string(), value.into());
+        self.params
+            .insert(key.to_string(), BuilderParam::Value(value.into()));
         self
     }
 
@@ -68,29 +233,54 @@ impl StepBuilder {
     ///
     /// Templates use Handlebars syntax: `{{ variable.path }}`
     pub fn with_template_param(mut self, key: &str, template: &str) -> Self {
-        // Mark as template by wrapping in special structure
-        self.step.params.insert(
+        self.params.insert(
             key.to_string(),
-            serde_json::json!({
-                "__template__": template
-            }),
+            BuilderParam::Param(Param::template(template)),
         );
         self
     }
 
+    /// Add a parameter that is passed through literally, even if it
+    /// contains template syntax.
+    pub fn with_raw_param<V: Into<Value>>(mut self, key: &str, value: V) -> Self {
+        self.params
+            .insert(key.to_string(), BuilderParam::Param(Param::literal(value)));
+        self
+    }
+
     /// Add all parameters from a JSON value.
     pub fn with_params(mut self, params: Value) -> Self {
         if let Value::Object(map) = params {
             for (k, v) in map {
-                self.step.params.insert(k, v);
+                self.params.insert(k, BuilderParam::Value(v));
             }
         }
         self
     }
 
+    /// Set the step id (referenced in templates as `{{ steps.<id>.result }}`).
+    pub fn id(mut self, id: &str) -> Self {
+        self.step.id = Some(id.to_string());
+        self
+    }
+
     /// Set the output variable name.
     pub fn output(mut self, name: &str) -> Self {
-        self.step.output = Some(name.to_string());
+        self.step.output = Some(Output::from(name));
+        self
+    }
+
+    /// Store the value at a dotted path of the result in a variable.
+    ///
+    /// Can be called repeatedly to bind several variables from one result.
+    pub fn output_path(mut self, name: &str, path: &str) -> Self {
+        let mut bindings = match self.step.output.take() {
+            Some(Output::Bindings(bindings)) => bindings,
+            Some(Output::Name(existing)) => IndexMap::from([(existing, String::new())]),
+            None => IndexMap::new(),
+        };
+        bindings.insert(name.to_string(), path.to_string());
+        self.step.output = Some(Output::Bindings(bindings));
         self
     }
 
@@ -100,9 +290,37 @@ impl StepBuilder {
         self
     }
 
-    /// Build the step.
+    /// Build the step, detecting templates in auto mode.
     pub fn build(self) -> Step {
-        self.step
+        self.build_with(TemplateMode::Auto)
+    }
+
+    /// Build the step, reading params and transform under a template mode.
+    pub(crate) fn build_with(self, mode: TemplateMode) -> Step {
+        let mut step = self.step;
+        step.params = self
+            .params
+            .into_iter()
+            .map(|(key, param)| (key, param.build(mode)))
+            .collect();
+        step.transform = self.transform.map(|transform| transform.build(mode));
+        step
+    }
+}
+
+impl From<Step> for StepBuilder {
+    /// Wrap a built step; its params are kept as they are.
+    fn from(mut step: Step) -> Self {
+        let params = std::mem::take(&mut step.params)
+            .into_iter()
+            .map(|(key, param)| (key, BuilderParam::Param(param)))
+            .collect();
+        let transform = step.transform.take().map(BuilderParam::Param);
+        Self {
+            step,
+            params,
+            transform,
+        }
     }
 }
 
@@ -125,8 +343,50 @@ mod tests {
 
         assert_eq!(step.service, "gmail");
         assert_eq!(step.method, "gmail.inbox");
-        assert_eq!(step.params.get("limit"), Some(&Value::from(10)));
-        assert_eq!(step.output, Some("emails".to_string()));
+        assert_eq!(step.params.get("limit"), Some(&Param::literal(10)));
+        assert_eq!(step.output, Some(Output::from("emails")));
+        assert_eq!(step.id, None);
+    }
+
+    #[test]
+    fn test_output_bindings() {
+        let step = Step::call("gmail", "gmail.inbox")
+            .output_path("emails", "messages")
+            .output_path("cursor", "next_page_token")
+            .build();
+
+        let result = serde_json::json!({
+            "messages": [{"id": "a"}],
+            "next_page_token": "tok"
+        });
+        let bound = step.output.unwrap().bind(&result);
+
+        assert_eq!(
+            bound,
+            vec![
+                ("emails".to_string(), serde_json::json!([{"id": "a"}])),
+                ("cursor".to_string(), Value::from("tok")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_missing_path_is_null() {
+        let output = Output::Bindings(IndexMap::from([(
+            "cursor".to_string(),
+            "next_page_token".to_string(),
+        )]));
+
+        let bound = output.bind(&serde_json::json!({"messages": []}));
+
+        assert_eq!(bound, vec![("cursor".to_string(), Value::Null)]);
+    }
+
+    #[test]
+    fn test_step_id() {
+        let step = Step::call("gmail", "gmail.inbox").id("inbox").build();
+
+        assert_eq!(step.id, Some("inbox".to_string()));
     }
 
     #[test]
@@ -136,6 +396,48 @@ mod tests {
             .build();
 
         let url_param = step.params.get("url").unwrap();
-        assert!(url_param.get("__template__").is_some());
+        assert_eq!(url_param, &Param::template("{{ emails.0.link }}"));
+    }
+
+    #[test]
+    fn test_builder_matches_yaml() {
+        let built = Step::call("browser", "browser.open")
+            .with_template_param("url", "{{ emails.0.link }}")
+            .build();
+        let yaml: Step = serde_yaml::from_str(
+            "service: browser\nmethod: browser.open\nparams:\n  url: \"{{ emails.0.link }}\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(built.params, yaml.params);
+        assert_eq!(
+            serde_yaml::to_string(&built.params).unwrap(),
+            "url: '{{ emails.0.link }}'\n"
+        );
+    }
+
+    #[test]
+    fn test_raw_param() {
+        let step = Step::call("render", "render.mustache")
+            .with_raw_param("template", "Hello {{ name }}")
+            .build();
+
+        let param = step.params.get("template").unwrap();
+        assert_eq!(param, &Param::literal("Hello {{ name }}"));
+    }
+
+    #[test]
+    fn test_transform_step() {
+        let step = Step::transform("{{ emails.messages }}")
+            .output("messages")
+            .build();
+
+        assert!(step.is_transform());
+        assert!(step.service.is_empty());
+        assert!(step.method.is_empty());
+        assert_eq!(
+            step.transform,
+            Some(Param::template("{{ emails.messages }}"))
+        );
     }
 }
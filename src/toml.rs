@@ -0,0 +1,80 @@
+//! TOML workflow parser.
+
+use crate::format::{load_file_as, parse_str, to_document, Format};
+use crate::Workflow;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Parse a workflow from TOML string.
+///
+/// Steps are written as `[[steps]]` tables. Templates and literals use the
+/// same `__template__`/`__raw__` markers as JSON.
+///
+/// # Example
+///
+/// ```rust
+/// use fgp_workflow::toml::parse_toml;
+///
+/// let toml = r#"
+/// name = "my-workflow"
+///
+/// [[steps]]
+/// service = "gmail"
+/// method = "gmail.inbox"
+/// output = "emails"
+/// params = { limit = 5 }
+/// "#;
+///
+/// let workflow = parse_toml(toml).unwrap();
+/// assert_eq!(workflow.name, "my-workflow");
+/// ```
+pub fn parse_toml(toml: &str) -> Result<Workflow> {
+    parse_str(toml, Format::Toml)
+}
+
+/// Load and parse a workflow from a TOML file.
+pub fn load_file(path: impl AsRef<Path>) -> Result<Workflow> {
+    load_file_as(path.as_ref(), Format::Toml)
+}
+
+/// Serialize a workflow to TOML.
+///
+/// TOML has no null, so workflows with `null` literals can't be written.
+pub fn to_toml(workflow: &Workflow) -> Result<String> {
+    let doc = to_document(workflow, false)?;
+    ::toml::to_string_pretty(&doc).context("Failed to serialize workflow TOML")
+}
+
+/// Save a workflow to a TOML file.
+pub fn save_file(workflow: &Workflow, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let toml = to_toml(workflow)?;
+
+    std::fs::write(path, toml)
+        .with_context(|| format!("Failed to write workflow file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Step;
+
+    #[test]
+    fn test_toml_round_trip() {
+        let workflow = Workflow::new("toml")
+            .description("Round trip")
+            .add(
+                Step::call("gmail", "gmail.search")
+                    .with_param("query", "from:{{ sender }}")
+                    .with_param("options", serde_json::json!({"limit": 5, "labels": ["a"]}))
+                    .with_raw_param("literal", "{{ x }}")
+                    .output_path("emails", "messages"),
+            )
+            .add(Step::transform("{{ emails.0 }}").id("first"))
+            .build();
+
+        let toml = to_toml(&workflow).unwrap();
+
+        assert_eq!(parse_toml(&toml).unwrap(), workflow);
+    }
+}
@@ -0,0 +1,550 @@
+//! Static workflow validation.
+//!
+//! [`check_structure`] finds the problems that stop a workflow from loading.
+//! [`validate`] also follows templates through the workflow: it reports
+//! template syntax errors, variables no earlier step or input provides,
+//! duplicate and reserved output names, and outputs nothing reads.
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::param::Param;
+use crate::step::{is_template, is_valid_name};
+use crate::Workflow;
+use handlebars::template::{HelperTemplate, Parameter, TemplateElement};
+use handlebars::Template;
+use indexmap::IndexMap;
+use std::collections::HashSet;
+
+/// Names the executor provides to templates, which inputs and outputs
+/// cannot use.
+pub(crate) const RESERVED_NAMES: &[&str] = &[
+    "prev", "$prev", "results", "$results", "steps", "$steps", "env", "secrets",
+];
+
+/// Check the structure of a workflow.
+///
+/// Every diagnostic returned is an error that prevents loading.
+pub(crate) fn check_structure(workflow: &Workflow) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if workflow.name.is_empty() {
+        diagnostics.push(Diagnostic::new("name", "Workflow name cannot be empty"));
+    }
+
+    if workflow.steps.is_empty() {
+        diagnostics.push(Diagnostic::new(
+            "steps",
+            "Workflow must have at least one step",
+        ));
+    }
+
+    if let Some(ref schedule) = workflow.schedule {
+        if let Err(e) = schedule.parse() {
+            diagnostics.push(Diagnostic::new("schedule.cron", e.to_string()));
+        }
+    }
+
+    for (name, output) in &workflow.outputs {
+        let what = format!("Workflow output '{}'", name);
+        for_each_template(
+            output,
+            &format!("outputs.{}", name),
+            &mut |template, path| check_syntax(template, path, &what, &mut diagnostics),
+        );
+    }
+
+    let mut ids = HashSet::new();
+
+    for (i, step) in workflow.steps.iter().enumerate() {
+        let mut fail = |field: &str, message: String| {
+            diagnostics.push(Diagnostic::new(format!("steps.{}{}", i, field), message));
+        };
+
+        if let Some(ref id) = step.id {
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            {
+                fail(".id", format!("Step {} has invalid id '{}'", i, id));
+            } else if !ids.insert(id) {
+                fail(".id", format!("Step {} has duplicate id '{}'", i, id));
+            }
+        }
+
+        if let Some(ref output) = step.output {
+            if output.names().iter().any(|name| name.is_empty()) {
+                fail(".output", format!("Step {} has empty output name", i));
+            }
+        }
+
+        if let Some(ref target) = step.workflow {
+            if step.is_transform() || !step.service.is_empty() || !step.method.is_empty() {
+                fail(
+                    "",
+                    format!("Step {} runs a workflow and cannot call a service", i),
+                );
+            }
+            if target.is_empty() {
+                fail(".workflow", format!("Step {} has empty workflow name", i));
+            }
+        } else if step.is_transform() {
+            if !step.service.is_empty() || !step.method.is_empty() {
+                fail(
+                    "",
+                    format!("Step {} is a transform and cannot call a service", i),
+                );
+            }
+            if !step.params.is_empty() {
+                fail(
+                    ".params",
+                    format!("Step {} is a transform and cannot have params", i),
+                );
+            }
+        } else {
+            for (field, name) in [("service", &step.service), ("method", &step.method)] {
+                if name.is_empty() {
+                    fail(
+                        &format!(".{}", field),
+                        format!("Step {} has empty {} name", i, field),
+                    );
+                } else if !is_template(name) && !is_valid_name(name) {
+                    fail(
+                        &format!(".{}", field),
+                        format!("Step {} has invalid {} name '{}'", i, field, name),
+                    );
+                }
+            }
+        }
+
+        let what = format!("Step {}", i);
+        for_each_step_template(step, i, &mut |template, path| {
+            check_syntax(template, path, &what, &mut diagnostics)
+        });
+    }
+
+    diagnostics
+}
+
+/// Validate a workflow in depth, without running it.
+///
+/// `variables` are set before the first step, like inputs (e.g. given on the
+/// command line without being declared).
+pub(crate) fn validate(workflow: &Workflow, variables: &[&str]) -> Vec<Diagnostic> {
+    let mut diagnostics = check_structure(workflow);
+    let mut warn = |path: String, message: String| {
+        diagnostics.push(Diagnostic::new(path, message).severity(Severity::Warning));
+    };
+
+    // Where each variable is set: inputs (None) or step outputs
+    let mut setters: IndexMap<&str, Vec<Option<usize>>> = IndexMap::new();
+    let scheduled = workflow.schedule.iter().flat_map(|s| s.inputs.keys());
+    let names = workflow.inputs.keys().chain(scheduled).map(String::as_str);
+    for name in names.chain(variables.iter().copied()) {
+        if !setters.contains_key(name) {
+            setters.entry(name).or_default().push(None);
+        }
+    }
+    for (i, step) in workflow.steps.iter().enumerate() {
+        for name in step.output.iter().flat_map(|o| o.names()) {
+            setters.entry(name).or_default().push(Some(i));
+        }
+    }
+
+    let mut errors = Vec::new();
+
+    for name in workflow.inputs.keys() {
+        if RESERVED_NAMES.contains(&name.as_str()) {
+            errors.push(Diagnostic::new(
+                format!("inputs.{}", name),
+                format!("Input '{}' uses a reserved name", name),
+            ));
+        }
+    }
+
+    for (name, setters) in &setters {
+        for setter in setters.iter().filter_map(|s| *s) {
+            let path = format!("steps.{}.output", setter);
+            if RESERVED_NAMES.contains(name) {
+                errors.push(Diagnostic::new(
+                    path,
+                    format!("Step {} output '{}' uses a reserved name", setter, name),
+                ));
+            } else if setters[0] != Some(setter) {
+                let first = match setters[0] {
+                    Some(first) => format!("step {}", first),
+                    None => "an input".to_string(),
+                };
+                warn(
+                    path,
+                    format!(
+                        "Step {} output '{}' overwrites the value set by {}",
+                        setter, name, first
+                    ),
+                );
+            }
+        }
+    }
+
+    // Check every template's references against what is set before it
+    let ids: Vec<Option<&str>> = workflow.steps.iter().map(|s| s.id.as_deref()).collect();
+    let mut used: HashSet<String> = HashSet::new();
+
+    for (i, step) in workflow.steps.iter().enumerate() {
+        let scope = Scope {
+            step: Some(i),
+            setters: &setters,
+            ids: &ids,
+        };
+        for_each_step_template(step, i, &mut |template, path| {
+            for reference in references(template) {
+                match scope.check(&reference) {
+                    Ok(Some(variable)) => {
+                        used.insert(variable.to_string());
+                    }
+                    Ok(None) => {}
+                    Err(message) => {
+                        errors.push(Diagnostic::new(path, format!("Step {} {}", i, message)))
+                    }
+                }
+            }
+        });
+    }
+
+    for (name, output) in &workflow.outputs {
+        let scope = Scope {
+            step: None,
+            setters: &setters,
+            ids: &ids,
+        };
+        for_each_template(
+            output,
+            &format!("outputs.{}", name),
+            &mut |template, path| {
+                for reference in references(template) {
+                    match scope.check(&reference) {
+                        Ok(Some(variable)) => {
+                            used.insert(variable.to_string());
+                        }
+                        Ok(None) => {}
+                        Err(message) => errors.push(Diagnostic::new(
+                            path,
+                            format!("Workflow output '{}' {}", name, message),
+                        )),
+                    }
+                }
+            },
+        );
+    }
+
+    for (name, setters) in &setters {
+        if used.contains(*name) || RESERVED_NAMES.contains(name) {
+            continue;
+        }
+        for setter in setters.iter().filter_map(|s| *s) {
+            warn(
+                format!("steps.{}.output", setter),
+                format!("Step {} output '{}' is never used", setter, name),
+            );
+        }
+    }
+
+    diagnostics.extend(errors);
+    diagnostics
+}
+
+/// What a template can reference at one point in a workflow.
+struct Scope<'a> {
+    /// Step the template belongs to (`None` for workflow outputs)
+    step: Option<usize>,
+    setters: &'a IndexMap<&'a str, Vec<Option<usize>>>,
+    ids: &'a [Option<&'a str>],
+}
+
+impl Scope<'_> {
+    /// Whether a step index runs before this point.
+    fn is_before(&self, index: usize) -> bool {
+        self.step.is_none_or(|step| index < step)
+    }
+
+    /// Check a reference, returning the variable it reads (if any) or a
+    /// description of the problem.
+    fn check<'r>(&self, reference: &'r str) -> Result<Option<&'r str>, String> {
+        let mut segments = reference.split('.');
+        let root = segments.next().unwrap_or_default();
+
+        match root {
+            "prev" | "$prev" if self.step == Some(0) => {
+                Err(format!("references '{}' but has no previous step", root))
+            }
+            "steps" => {
+                let Some(id) = segments.next() else {
+                    return Ok(None);
+                };
+                match self.ids.iter().position(|s| *s == Some(id)) {
+                    Some(index) if self.is_before(index) => Ok(None),
+                    Some(index) => Err(format!(
+                        "references step '{}', which runs later (step {})",
+                        id, index
+                    )),
+                    None => Err(format!("references unknown step '{}'", id)),
+                }
+            }
+            _ if RESERVED_NAMES.contains(&root) => Ok(None),
+            _ => {
+                let setters = self.setters.get(root).map(Vec::as_slice).unwrap_or(&[]);
+                if setters.iter().any(|s| s.is_none_or(|s| self.is_before(s))) {
+                    Ok(Some(root))
+                } else if let Some(Some(later)) = setters.first() {
+                    Err(format!(
+                        "uses '{}' before it is set by step {}",
+                        root, later
+                    ))
+                } else {
+                    Err(format!(
+                        "references '{}', which no earlier step or input sets",
+                        root
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Report a template that doesn't compile.
+fn check_syntax(template: &str, path: &str, what: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if let Err(e) = Template::compile(template) {
+        diagnostics.push(Diagnostic::new(
+            path,
+            format!(
+                "{} has invalid template {:?}: {}",
+                what,
+                template,
+                e.reason()
+            ),
+        ));
+    }
+}
+
+/// Call `f` with each template of a step and its document path.
+pub(crate) fn for_each_step_template(
+    step: &crate::Step,
+    index: usize,
+    f: &mut dyn FnMut(&str, &str),
+) {
+    for (field, name) in [("service", &step.service), ("method", &step.method)] {
+        if is_template(name) {
+            f(name, &format!("steps.{}.{}", index, field));
+        }
+    }
+    for (key, param) in &step.params {
+        for_each_template(param, &format!("steps.{}.params.{}", index, key), f);
+    }
+    if let Some(ref transform) = step.transform {
+        for_each_template(transform, &format!("steps.{}.transform", index), f);
+    }
+}
+
+/// Call `f` with each template in a param and its document path.
+pub(crate) fn for_each_template(param: &Param, path: &str, f: &mut dyn FnMut(&str, &str)) {
+    match param {
+        Param::Literal(_) => {}
+        Param::Template(template) => f(template, path),
+        Param::Object(fields) => {
+            for (key, value) in fields {
+                for_each_template(value, &format!("{}.{}", path, key), f);
+            }
+        }
+        Param::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                for_each_template(item, &format!("{}.{}", path, i), f);
+            }
+        }
+    }
+}
+
+/// Dotted paths of the context values a template reads.
+///
+/// References inside `#each` and `#with` blocks are relative to the block's
+/// value and are skipped.
+pub(crate) fn references(template: &str) -> Vec<String> {
+    let mut references = Vec::new();
+    if let Ok(template) = Template::compile(template) {
+        collect_elements(&template.elements, &mut references);
+    }
+    references
+}
+
+fn collect_elements(elements: &[TemplateElement], references: &mut Vec<String>) {
+    for element in elements {
+        match element {
+            TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                if helper.params.is_empty() && helper.hash.is_empty() {
+                    collect_parameter(&helper.name, references);
+                } else {
+                    collect_arguments(helper, references);
+                }
+            }
+            TemplateElement::HelperBlock(helper) => {
+                collect_arguments(helper, references);
+
+                let changes_scope = helper.block_param.is_some()
+                    || matches!(helper.name, Parameter::Name(ref name) if name == "each" || name == "with");
+                if !changes_scope {
+                    if let Some(ref block) = helper.template {
+                        collect_elements(&block.elements, references);
+                    }
+                }
+                if let Some(ref inverse) = helper.inverse {
+                    collect_elements(&inverse.elements, references);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collect_arguments(helper: &HelperTemplate, references: &mut Vec<String>) {
+    for parameter in helper.params.iter().chain(helper.hash.values()) {
+        collect_parameter(parameter, references);
+    }
+}
+
+fn collect_parameter(parameter: &Parameter, references: &mut Vec<String>) {
+    let raw = match parameter {
+        Parameter::Name(name) => name.as_str(),
+        Parameter::Path(handlebars::Path::Relative((_, raw))) => raw.as_str(),
+        Parameter::Subexpression(sub) => {
+            collect_elements(std::slice::from_ref(sub.element.as_ref()), references);
+            return;
+        }
+        _ => return,
+    };
+
+    let path = raw
+        .strip_prefix("this.")
+        .or_else(|| raw.strip_prefix("./"))
+        .unwrap_or(raw)
+        .replace('/', ".");
+    if !path.is_empty() && path != "this" && !path.starts_with('@') && !path.starts_with("..") {
+        references.push(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Step;
+
+    fn messages(workflow: &Workflow, severity: Severity) -> Vec<String> {
+        validate(workflow, &[])
+            .into_iter()
+            .filter(|d| d.severity == severity)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_references() {
+        assert_eq!(references("{{ emails.0.url }}"), ["emails.0.url"]);
+        assert_eq!(
+            references("{{#if ready}}{{ name }}{{else}}{{ steps.fallback.result }}{{/if}}"),
+            ["ready", "name", "steps.fallback.result"]
+        );
+        assert_eq!(
+            references("{{#each items}}{{ this.name }} {{ @index }}{{/each}}"),
+            ["items"]
+        );
+        assert_eq!(references("{{ lookup data key }}"), ["data", "key"]);
+    }
+
+    #[test]
+    fn test_valid_workflow() {
+        let workflow = Workflow::new("ok")
+            .input("account")
+            .output("count", "{{ count }}")
+            .add(
+                Step::call("gmail", "gmail.inbox")
+                    .with_param("account", "{{ account }}")
+                    .id("inbox")
+                    .output("emails"),
+            )
+            .add(Step::transform("{{ emails.0 }}").output("count"))
+            .add(Step::transform("{{ steps.inbox.status }} {{ prev }}"))
+            .build();
+
+        assert_eq!(validate(&workflow, &[]), Vec::new());
+    }
+
+    #[test]
+    fn test_undefined_references() {
+        let workflow = Workflow::new("refs")
+            .add(Step::transform("{{ prev }}"))
+            .add(Step::transform("{{ later }} {{ missing }}"))
+            .add(Step::transform("{{ steps.last.result }} {{ steps.nope }}"))
+            .add(Step::transform(1).id("last").output("later"))
+            .build();
+
+        assert_eq!(
+            messages(&workflow, Severity::Error),
+            [
+                "Step 0 references 'prev' but has no previous step",
+                "Step 1 uses 'later' before it is set by step 3",
+                "Step 1 references 'missing', which no earlier step or input sets",
+                "Step 2 references step 'last', which runs later (step 3)",
+                "Step 2 references unknown step 'nope'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_names() {
+        let workflow = Workflow::new("outputs")
+            .input("query")
+            .add(Step::transform(1).output("prev"))
+            .add(Step::transform(2).output("query"))
+            .add(Step::transform("{{ query }}").output("unused"))
+            .build();
+
+        assert_eq!(
+            messages(&workflow, Severity::Error),
+            ["Step 0 output 'prev' uses a reserved name"]
+        );
+        assert_eq!(
+            messages(&workflow, Severity::Warning),
+            [
+                "Step 1 output 'query' overwrites the value set by an input",
+                "Step 2 output 'unused' is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_syntax() {
+        let workflow = Workflow::new("syntax")
+            .add(Step::transform(serde_json::json!({"a": ["{{#if x}}"]})))
+            .build();
+
+        let diagnostics = validate(&workflow, &[]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "steps.0.transform.a.0");
+        assert!(diagnostics[0].message.contains("invalid template"));
+    }
+
+    #[test]
+    fn test_schedule_inputs_are_set() {
+        let workflow = crate::parse_yaml(
+            r#"
+name: scheduled
+schedule:
+  cron: "@daily"
+  inputs:
+    account: work
+steps:
+  - set: "{{ account }}"
+"#,
+        )
+        .unwrap();
+
+        assert!(workflow.validate().is_empty());
+    }
+}
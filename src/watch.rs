@@ -0,0 +1,522 @@
+//! Re-running workflows when their files change.
+//!
+//! A [`Watcher`] polls a workflow file and the files it includes. On each
+//! change it reloads the workflow, validates it and, when validation finds
+//! no errors, runs it again (or plans it with [`dry_run`](crate::dry_run)),
+//! reporting how the step results differ from the previous run:
+//!
+//! ```rust,no_run
+//! use fgp_workflow::watch::Watcher;
+//! use std::ops::ControlFlow;
+//!
+//! Watcher::new("workflows/digest.yaml")
+//!     .dry_run(true)
+//!     .watch(|update| {
+//!         for change in update.changes.iter().flatten() {
+//!             println!("{}", change);
+//!         }
+//!         ControlFlow::Continue(())
+//!     });
+//! ```
+
+use crate::diagnostic::Diagnostic;
+use crate::format::load_file_tracking;
+use crate::{Context, ExecutionResult, Format, StepFailure, StepResult, Workflow};
+use chrono::{DateTime, Local};
+use serde_json::Value;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often files are checked by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a workflow file and re-runs it when it or its includes change.
+pub struct Watcher {
+    path: PathBuf,
+    dry_run: bool,
+    interval: Duration,
+    context: Box<dyn Fn() -> Context>,
+
+    /// Files read by the last load, with their state before it
+    files: Vec<(PathBuf, Option<Stamp>)>,
+
+    /// Steps of the last run, to compare the next run against
+    previous: Option<Vec<Snapshot>>,
+}
+
+/// Modification time and size of a file.
+pub(crate) type Stamp = (SystemTime, u64);
+
+/// What happened after a change.
+#[derive(Debug)]
+pub struct Update {
+    /// Files that changed (the workflow file for the first update)
+    pub changed: Vec<PathBuf>,
+
+    /// The reloaded workflow, unless it failed to load
+    pub workflow: Option<Workflow>,
+
+    /// Problems found by validating the workflow
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// Whether and how the workflow ran
+    pub outcome: Outcome,
+
+    /// When the run started, if the workflow ran
+    pub started_at: Option<DateTime<Local>>,
+
+    /// How the step results differ from the previous run, if there was one
+    /// and the workflow ran this time
+    pub changes: Option<Vec<StepChange>>,
+}
+
+/// Result of reloading and running a workflow.
+#[derive(Debug)]
+pub enum Outcome {
+    /// The workflow file could not be loaded.
+    LoadFailed(anyhow::Error),
+
+    /// Validation found errors, so the workflow was not run.
+    Invalid,
+
+    /// The workflow ran (or was planned).
+    Completed(Box<ExecutionResult>),
+
+    /// A step failed; the error carries a [`StepFailure`].
+    Failed(anyhow::Error),
+}
+
+/// A difference between the step results of two runs.
+///
+/// Steps are numbered by index; steps of a sub-workflow are numbered
+/// `parent.child` (`2.0`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepChange {
+    /// A step the previous run did not reach.
+    Added { step: String, label: String },
+
+    /// A step this run did not reach.
+    Removed { step: String, label: String },
+
+    /// A value of a step differs. `path` is relative to the step, such as
+    /// `params.limit` or `result.messages.0.id`; `None` means absent.
+    Changed {
+        step: String,
+        label: String,
+        path: String,
+        before: Option<Value>,
+        after: Option<Value>,
+    },
+}
+
+impl Watcher {
+    /// Watch a workflow file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            dry_run: false,
+            interval: DEFAULT_INTERVAL,
+            context: Box::new(Context::new),
+            files: Vec::new(),
+            previous: None,
+        }
+    }
+
+    /// Plan the workflow instead of running it.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// How often to check the files.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Build the context each run starts with (inputs, secrets, workflows).
+    pub fn context<F: Fn() -> Context + 'static>(mut self, context: F) -> Self {
+        self.context = Box::new(context);
+        self
+    }
+
+    /// Files being watched: the workflow file and the files it included when
+    /// it was last loaded.
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().map(|(file, _)| file)
+    }
+
+    /// Check the files once, reloading and running the workflow if any
+    /// changed. The first call always runs it.
+    pub fn poll(&mut self) -> Option<Update> {
+        let current: Vec<Option<Stamp>> = self.files.iter().map(|(f, _)| stamp(f)).collect();
+
+        let changed: Vec<PathBuf> = if self.files.is_empty() {
+            vec![self.path.clone()]
+        } else {
+            self.files
+                .iter()
+                .zip(&current)
+                .filter(|((_, before), now)| before != *now)
+                .map(|((file, _), _)| file.clone())
+                .collect()
+        };
+        if changed.is_empty() {
+            return None;
+        }
+
+        // Remember the state from before loading, so a save during the run
+        // is noticed by the next poll
+        let mut files = Vec::new();
+        let loaded = Format::from_path_or_err(&self.path)
+            .and_then(|format| load_file_tracking(&self.path, format, &mut files));
+        if files.is_empty() {
+            files.push(self.path.clone());
+        }
+        self.files = files
+            .into_iter()
+            .map(|file| {
+                let known = self.files.iter().position(|(f, _)| *f == file);
+                let before = match known {
+                    Some(i) => current[i],
+                    None => stamp(&file),
+                };
+                (file, before)
+            })
+            .collect();
+
+        Some(self.update(changed, loaded))
+    }
+
+    /// Poll the files until `on_update` breaks, sleeping between checks.
+    pub fn watch<F: FnMut(&Update) -> ControlFlow<()>>(&mut self, mut on_update: F) {
+        loop {
+            if let Some(update) = self.poll() {
+                if on_update(&update).is_break() {
+                    return;
+                }
+            }
+            std::thread::sleep(self.interval);
+        }
+    }
+
+    /// Validate and run a reloaded workflow.
+    fn update(&mut self, changed: Vec<PathBuf>, loaded: anyhow::Result<Workflow>) -> Update {
+        let workflow = match loaded {
+            Ok(workflow) => workflow,
+            Err(error) => {
+                return Update {
+                    changed,
+                    workflow: None,
+                    diagnostics: Vec::new(),
+                    outcome: Outcome::LoadFailed(error),
+                    started_at: None,
+                    changes: None,
+                };
+            }
+        };
+
+        // Validate against the context the run starts with, so inputs given
+        // without being declared count as set
+        let ctx = (self.context)();
+        let diagnostics = workflow.validate_with_context(&ctx);
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Update {
+                changed,
+                workflow: Some(workflow),
+                diagnostics,
+                outcome: Outcome::Invalid,
+                started_at: None,
+                changes: None,
+            };
+        }
+
+        let started_at = Local::now();
+        let ran = if self.dry_run {
+            crate::dry_run(&workflow, ctx)
+        } else {
+            crate::execute_with_context(&workflow, ctx)
+        };
+
+        let (outcome, steps) = match ran {
+            Ok(result) => {
+                let steps = snapshot(&result.step_results);
+                (Outcome::Completed(Box::new(result)), steps)
+            }
+            Err(error) => {
+                let steps = error
+                    .downcast_ref::<StepFailure>()
+                    .map(|failure| snapshot(&failure.step_results))
+                    .unwrap_or_default();
+                (Outcome::Failed(error), steps)
+            }
+        };
+
+        let changes = self
+            .previous
+            .as_ref()
+            .map(|previous| compare(previous, &steps));
+        self.previous = Some(steps);
+
+        Update {
+            changed,
+            workflow: Some(workflow),
+            diagnostics,
+            outcome,
+            started_at: Some(started_at),
+            changes,
+        }
+    }
+}
+
+pub(crate) fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Compare the step results of two runs.
+///
+/// Durations are not compared.
+pub fn diff(before: &[StepResult], after: &[StepResult]) -> Vec<StepChange> {
+    compare(&snapshot(before), &snapshot(after))
+}
+
+/// The compared parts of a step result.
+#[derive(Debug)]
+struct Snapshot {
+    step: String,
+    label: String,
+    values: Value,
+}
+
+/// Flatten step results, children after their parent.
+fn snapshot(steps: &[StepResult]) -> Vec<Snapshot> {
+    fn add(steps: &[StepResult], prefix: &str, out: &mut Vec<Snapshot>) {
+        for step in steps {
+            let number = format!("{}{}", prefix, step.index);
+            out.push(Snapshot {
+                step: number.clone(),
+                label: step.step.label(),
+                values: serde_json::json!({"params": step.params, "result": step.result}),
+            });
+            add(&step.children, &format!("{}.", number), out);
+        }
+    }
+
+    let mut out = Vec::new();
+    add(steps, "", &mut out);
+    out
+}
+
+fn compare(before: &[Snapshot], after: &[Snapshot]) -> Vec<StepChange> {
+    let mut changes = Vec::new();
+
+    for old in before {
+        let new = after.iter().find(|s| s.step == old.step);
+        match new {
+            Some(new) if new.label == old.label => {
+                let mut values = Vec::new();
+                diff_values("", &old.values, &new.values, &mut values);
+                changes.extend(values.into_iter().map(|(path, before, after)| {
+                    StepChange::Changed {
+                        step: old.step.clone(),
+                        label: old.label.clone(),
+                        path,
+                        before,
+                        after,
+                    }
+                }));
+            }
+            _ => changes.push(StepChange::Removed {
+                step: old.step.clone(),
+                label: old.label.clone(),
+            }),
+        }
+    }
+
+    for new in after {
+        let kept = before
+            .iter()
+            .any(|s| s.step == new.step && s.label == new.label);
+        if !kept {
+            changes.push(StepChange::Added {
+                step: new.step.clone(),
+                label: new.label.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Collect the paths at which two values differ.
+fn diff_values(
+    path: &str,
+    before: &Value,
+    after: &Value,
+    out: &mut Vec<(String, Option<Value>, Option<Value>)>,
+) {
+    let join = |key: &dyn fmt::Display| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match (before, after) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, old) in a {
+                match b.get(key) {
+                    Some(new) => diff_values(&join(key), old, new, out),
+                    None => out.push((join(key), Some(old.clone()), None)),
+                }
+            }
+            for (key, new) in b {
+                if !a.contains_key(key) {
+                    out.push((join(key), None, Some(new.clone())));
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                match (a.get(i), b.get(i)) {
+                    (Some(old), Some(new)) => diff_values(&join(&i), old, new, out),
+                    (old, new) => out.push((join(&i), old.cloned(), new.cloned())),
+                }
+            }
+        }
+        _ if before != after => {
+            out.push((path.to_string(), Some(before.clone()), Some(after.clone())))
+        }
+        _ => {}
+    }
+}
+
+/// One line per change:
+///
+/// ```text
+/// ~ [1] gmail.inbox result.total: 3 -> 4
+/// + [2] browser.open
+/// ```
+impl fmt::Display for StepChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(none)".to_string(),
+        };
+
+        match self {
+            StepChange::Added { step, label } => write!(f, "+ [{}] {}", step, label),
+            StepChange::Removed { step, label } => write!(f, "- [{}] {}", step, label),
+            StepChange::Changed {
+                step,
+                label,
+                path,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ [{}] {} {}: {} -> {}",
+                step,
+                label,
+                path,
+                show(before),
+                show(after)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_values() {
+        let mut out = Vec::new();
+        diff_values(
+            "",
+            &serde_json::json!({"a": 1, "b": [1, 2], "c": {"d": true}}),
+            &serde_json::json!({"a": 2, "b": [1], "c": {"d": true}, "e": null}),
+            &mut out,
+        );
+
+        assert_eq!(
+            out,
+            vec![
+                ("a".to_string(), Some(Value::from(1)), Some(Value::from(2))),
+                ("b.1".to_string(), Some(Value::from(2)), None),
+                ("e".to_string(), None, Some(Value::Null)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_reruns_on_change() {
+        let dir = crate::testing::TempDir::new("watch");
+        let main = dir.join("main.yaml");
+        let part = dir.join("part.yaml");
+        std::fs::write(
+            &main,
+            "name: main
+steps:
+  - include: part.yaml
+  - set: \"{{ count }}\"
+",
+        )
+        .unwrap();
+        std::fs::write(&part, "steps:\n  - set: 1\n    output: count\n").unwrap();
+
+        let mut watcher = Watcher::new(&main);
+
+        let first = watcher.poll().unwrap();
+        assert!(matches!(first.outcome, Outcome::Completed(_)));
+        assert!(first.started_at.is_some());
+        assert_eq!(first.changes, None);
+        assert_eq!(watcher.files().count(), 2);
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&part, "steps:\n  - set: 22\n    output: count\n").unwrap();
+        let second = watcher.poll().unwrap();
+        assert_eq!(second.changed, vec![part.clone()]);
+        assert_eq!(
+            second
+                .changes
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "~ [0] transform result: 1 -> 22",
+                "~ [1] transform result: 1 -> 22",
+            ]
+        );
+
+        std::fs::write(&part, "steps:\n  - set: [1\n").unwrap();
+        let broken = watcher.poll().unwrap();
+        assert!(matches!(broken.outcome, Outcome::LoadFailed(_)));
+
+        std::fs::write(&part, "steps:\n  - set: \"{{ missing }}\"\n").unwrap();
+        let invalid = watcher.poll().unwrap();
+        assert!(matches!(invalid.outcome, Outcome::Invalid));
+        assert!(invalid.started_at.is_none());
+        assert!(invalid.diagnostics.iter().any(Diagnostic::is_error));
+
+        std::fs::write(
+            &part,
+            "steps:\n  - set: \"{{ missing }}\"\n    output: count\n",
+        )
+        .unwrap();
+        let mut watcher = Watcher::new(&main).context(|| {
+            let mut ctx = Context::new();
+            ctx.set("missing", Value::from("given"));
+            ctx
+        });
+        let given = watcher.poll().unwrap();
+        assert!(given.diagnostics.is_empty());
+        assert!(matches!(given.outcome, Outcome::Completed(_)));
+    }
+}
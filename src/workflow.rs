@@ -1,20 +1,129 @@
 //! Workflow definition and builder.
 
+use crate::diagnostic::{Diagnostic, Locator, Origin};
+use crate::manifest::Manifests;
+use crate::param::Param;
+use crate::schedule::Schedule;
 use crate::step::{Step, StepBuilder};
-use serde::{Deserialize, Serialize};
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
 
 /// A workflow consisting of multiple steps.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Workflow {
     /// Workflow name
     pub name: String,
 
     /// Description of what this workflow does
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
+    /// How template strings in params are recognized when reading files
+    #[serde(default, skip_serializing_if = "TemplateMode::is_auto")]
+    pub templates: TemplateMode,
+
+    /// Variables the workflow expects to be set before it runs
+    #[serde(
+        default,
+        skip_serializing_if = "IndexMap::is_empty",
+        deserialize_with = "deserialize_inputs"
+    )]
+    #[schemars(with = "IndexMap<String, Option<Input>>")]
+    pub inputs: IndexMap<String, Input>,
+
+    /// Values the workflow returns, resolved after the last step.
+    ///
+    /// When empty, the workflow returns the last step's result.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub outputs: IndexMap<String, Param>,
+
+    /// When the workflow runs on its own: a cron expression, or a mapping
+    /// with `cron`, `overlap` and `inputs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+
     /// Steps to execute
     pub steps: Vec<Step>,
+
+    /// File the workflow was loaded from, if any
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+
+    /// Where each step was defined, for diagnostics
+    #[serde(skip)]
+    pub(crate) origins: Vec<Option<Origin>>,
+}
+
+impl PartialEq for Workflow {
+    /// Workflows are equal if their definitions are, wherever they were
+    /// loaded from.
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.description == other.description
+            && self.templates == other.templates
+            && self.inputs == other.inputs
+            && self.outputs == other.outputs
+            && self.schedule == other.schedule
+            && self.steps == other.steps
+    }
+}
+
+/// A declared workflow input.
+///
+/// ```yaml
+/// inputs:
+///   account:
+///     default: personal
+///   query:                # required
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Input {
+    /// What the input is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Value used when the input is not provided; inputs without a default
+    /// are required
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+}
+
+/// Read inputs, allowing `name:` with no body for a required input.
+fn deserialize_inputs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<IndexMap<String, Input>, D::Error> {
+    let inputs = IndexMap::<String, Option<Input>>::deserialize(deserializer)?;
+    Ok(inputs
+        .into_iter()
+        .map(|(name, input)| (name, input.unwrap_or_default()))
+        .collect())
+}
+
+/// How bare template strings in params and transforms are read from
+/// workflow files and builders.
+///
+/// Once loaded or built, params are [`Param`](crate::Param) values that
+/// already know whether they are templates; the mode only affects reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateMode {
+    /// Any string containing `{{` and `}}` is a template.
+    #[default]
+    Auto,
+
+    /// Only values marked as templates (`!template` in YAML or
+    /// `{"__template__": ...}`) are rendered; other strings are literal.
+    Explicit,
+}
+
+impl TemplateMode {
+    /// Whether this is the default auto-detection mode.
+    pub fn is_auto(&self) -> bool {
+        *self == TemplateMode::Auto
+    }
 }
 
 impl Workflow {
@@ -29,10 +138,96 @@ impl Workflow {
         Self {
             name: name.to_string(),
             description: None,
+            templates: TemplateMode::Auto,
+            inputs: IndexMap::new(),
+            outputs: IndexMap::new(),
+            schedule: None,
             steps: Vec::new(),
+            source: None,
+            origins: Vec::new(),
         }
     }
 
+    /// Check the workflow for problems without running it.
+    ///
+    /// Reports invalid template syntax, variables that no earlier step or
+    /// input sets, references to unknown or later steps, reserved and
+    /// overwritten output names, and outputs that are never used. Diagnostics
+    /// are located in the workflow's file when it was loaded from one.
+    ///
+    /// ```rust
+    /// use fgp_workflow::{Step, Workflow};
+    ///
+    /// let workflow = Workflow::new("check")
+    ///     .add(Step::transform("{{ missing }}"))
+    ///     .build();
+    ///
+    /// let diagnostics = workflow.validate();
+    /// assert!(diagnostics[0].is_error());
+    /// ```
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let locator = Locator::workflow(self);
+        crate::validate::validate(self, &[])
+            .into_iter()
+            .map(|d| locator.locate(d))
+            .collect()
+    }
+
+    /// Check the workflow like [`validate`](Self::validate), counting the
+    /// variables already set in `ctx` as inputs, as a run with that context
+    /// would see them.
+    ///
+    /// ```rust
+    /// use fgp_workflow::{Context, Step, Workflow};
+    ///
+    /// let workflow = Workflow::new("check")
+    ///     .add(Step::transform("{{ account }}"))
+    ///     .build();
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.set("account", "work".into());
+    /// assert!(workflow.validate_with_context(&ctx).is_empty());
+    /// ```
+    pub fn validate_with_context(&self, ctx: &crate::Context) -> Vec<Diagnostic> {
+        let locator = Locator::workflow(self);
+        crate::validate::validate(self, &ctx.variable_names())
+            .into_iter()
+            .map(|d| locator.locate(d))
+            .collect()
+    }
+
+    /// Check the workflow like [`validate`](Self::validate), and also check
+    /// each service step's method and params against service manifests.
+    ///
+    /// ```rust,no_run
+    /// use fgp_workflow::manifest::Manifests;
+    ///
+    /// let workflow = fgp_workflow::load_file("workflows/digest.yaml")?;
+    /// let mut manifests = Manifests::load_dir("manifests")?;
+    /// manifests.fetch_missing(&workflow);
+    ///
+    /// let diagnostics = workflow.validate_with(&manifests);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn validate_with(&self, manifests: &Manifests) -> Vec<Diagnostic> {
+        let locator = Locator::workflow(self);
+        crate::validate::validate(self, &[])
+            .into_iter()
+            .chain(manifests.check(self))
+            .map(|d| locator.locate(d))
+            .collect()
+    }
+
+    /// Serialize this workflow to YAML (see [`crate::yaml::to_yaml`]).
+    pub fn to_yaml(&self) -> anyhow::Result<String> {
+        crate::yaml::to_yaml(self)
+    }
+
+    /// Serialize this workflow to JSON (see [`crate::json::to_json`]).
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        crate::json::to_json(self)
+    }
+
     /// Execute this workflow.
     pub fn run(&self) -> anyhow::Result<crate::ExecutionResult> {
         crate::execute(self)
@@ -40,20 +235,23 @@ impl Workflow {
 }
 
 /// Builder for creating workflows.
+///
+/// Steps and outputs are converted to params when the workflow is built, so
+/// the [`templates`](Self::templates) mode applies wherever it is set.
 #[derive(Debug, Clone)]
 pub struct WorkflowBuilder {
     workflow: Workflow,
+    steps: Vec<StepBuilder>,
+    outputs: IndexMap<String, Value>,
 }
 
 impl WorkflowBuilder {
     /// Create a new workflow builder.
     pub fn new(name: &str) -> Self {
         Self {
-            workflow: Workflow {
-                name: name.to_string(),
-                description: None,
-                steps: Vec::new(),
-            },
+            workflow: Workflow::empty(name),
+            steps: Vec::new(),
+            outputs: IndexMap::new(),
         }
     }
 
@@ -63,21 +261,79 @@ impl WorkflowBuilder {
         self
     }
 
+    /// Set how template strings are recognized in the params, transforms
+    /// and outputs given to this builder, and when reading the workflow back
+    /// from a file.
+    ///
+    /// With [`TemplateMode::Explicit`], only
+    /// [`with_template_param`](StepBuilder::with_template_param) values and
+    /// `{"__template__": ...}` objects are templates. Steps added already
+    /// built keep their params.
+    pub fn templates(mut self, mode: TemplateMode) -> Self {
+        self.workflow.templates = mode;
+        self
+    }
+
+    /// Declare a required input.
+    pub fn input(mut self, name: &str) -> Self {
+        self.workflow
+            .inputs
+            .insert(name.to_string(), Input::default());
+        self
+    }
+
+    /// Declare an optional input with a default value.
+    pub fn input_default<V: Into<Value>>(mut self, name: &str, default: V) -> Self {
+        let input = Input {
+            default: Some(default.into()),
+            ..Input::default()
+        };
+        self.workflow.inputs.insert(name.to_string(), input);
+        self
+    }
+
+    /// Declare an output returned by the workflow.
+    ///
+    /// Strings containing `{{ }}` are treated as templates, as in YAML.
+    pub fn output<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.outputs.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Run the workflow on a schedule (see [`crate::schedule`]).
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.workflow.schedule = Some(schedule);
+        self
+    }
+
     /// Add a step to the workflow.
     #[allow(clippy::should_implement_trait)]
-    pub fn add<S: Into<Step>>(mut self, step: S) -> Self {
-        self.workflow.steps.push(step.into());
+    pub fn add<S: Into<StepBuilder>>(mut self, step: S) -> Self {
+        self.steps.push(step.into());
         self
     }
 
     /// Add a step builder (convenience).
     pub fn step(self, step: StepBuilder) -> Self {
-        self.add(step.build())
+        self.add(step)
     }
 
     /// Build the workflow.
     pub fn build(self) -> Workflow {
-        self.workflow
+        let mut workflow = self.workflow;
+        let mode = workflow.templates;
+
+        workflow.steps = self
+            .steps
+            .into_iter()
+            .map(|step| step.build_with(mode))
+            .collect();
+        workflow.outputs = self
+            .outputs
+            .into_iter()
+            .map(|(name, value)| (name, Param::from_builder(value, mode)))
+            .collect();
+        workflow
     }
 
     /// Execute the workflow.
@@ -118,4 +374,71 @@ mod tests {
         assert_eq!(workflow.steps[0].service, "gmail");
         assert_eq!(workflow.steps[1].service, "browser");
     }
+
+    #[test]
+    fn test_builder_explicit_templates() {
+        let workflow = Workflow::new("explicit")
+            .add(
+                Step::call("render", "render.mustache")
+                    .with_param("body", "Hello {{ name }}")
+                    .with_template_param("to", "{{ user.email }}"),
+            )
+            .output("greeting", "{{ name }}")
+            .templates(TemplateMode::Explicit)
+            .build();
+
+        let params = &workflow.steps[0].params;
+        assert_eq!(params["body"], Param::literal("Hello {{ name }}"));
+        assert_eq!(params["to"], Param::template("{{ user.email }}"));
+        assert_eq!(workflow.outputs["greeting"], Param::literal("{{ name }}"));
+    }
+
+    #[test]
+    fn test_to_json_round_trip() {
+        let workflow = Workflow::new("json")
+            .add(
+                Step::call("gmail", "gmail.search")
+                    .with_param("query", "{{ query }}")
+                    .with_raw_param("raw", "{{ literal }}"),
+            )
+            .build();
+
+        let json = workflow.to_json().unwrap();
+        let parsed: Workflow = serde_json::from_str(&json).unwrap();
+
+        assert!(json.contains(
+            r#""raw": {
+          "__raw__": "{{ literal }}"
+        }"#
+        ));
+        assert_eq!(parsed, workflow);
+    }
+
+    #[test]
+    fn test_inputs_and_outputs_from_yaml() {
+        let workflow = crate::parse_yaml(
+            r#"
+name: fetch
+inputs:
+  account:
+    default: personal
+  query:
+outputs:
+  emails: "{{ inbox.messages }}"
+steps:
+  - set: {messages: []}
+    output: inbox
+"#,
+        )
+        .unwrap();
+
+        let expected = Workflow::new("fetch")
+            .input_default("account", "personal")
+            .input("query")
+            .output("emails", "{{ inbox.messages }}")
+            .add(Step::transform(serde_json::json!({"messages": []})).output("inbox"))
+            .build();
+
+        assert_eq!(workflow, expected);
+    }
 }
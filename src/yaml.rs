@@ -1,5 +1,7 @@
 //! YAML workflow parser.
 
+use crate::format::{load_file_as, parse_str, to_document, Format};
+use crate::param::{RAW_KEY, TEMPLATE_KEY};
 use crate::Workflow;
 use anyhow::{Context, Result};
 use std::path::Path;
@@ -26,11 +28,7 @@ use std::path::Path;
 /// assert_eq!(workflow.steps.len(), 1);
 /// ```
 pub fn parse_yaml(yaml: &str) -> Result<Workflow> {
-    let workflow: Workflow = serde_yaml::from_str(yaml).context("Failed to parse workflow YAML")?;
-
-    validate(&workflow)?;
-
-    Ok(workflow)
+    parse_str(yaml, Format::Yaml)
 }
 
 /// Load and parse a workflow from a YAML file.
@@ -47,39 +45,91 @@ pub fn parse_yaml(yaml: &str) -> Result<Workflow> {
 /// # Ok::<(), anyhow::Error>(())
 /// ```
 pub fn load_file(path: impl AsRef<Path>) -> Result<Workflow> {
+    load_file_as(path.as_ref(), Format::Yaml)
+}
+
+/// Serialize a workflow to YAML.
+///
+/// Templates are written as bare `"{{ ... }}"` strings (`!template` in
+/// explicit mode) and literals that contain template syntax as `!raw`, so
+/// [`parse_yaml`] reads the result back identically.
+///
+/// # Example
+///
+/// ```rust
+/// use fgp_workflow::{parse_yaml, yaml::to_yaml, Step, Workflow};
+///
+/// let workflow = Workflow::new("open-first")
+///     .add(Step::call("browser", "browser.open")
+///         .with_template_param("url", "{{ emails.0.url }}"))
+///     .build();
+///
+/// let yaml = to_yaml(&workflow)?;
+/// assert_eq!(parse_yaml(&yaml)?, workflow);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn to_yaml(workflow: &Workflow) -> Result<String> {
+    let doc = to_document(workflow, true)?;
+    serde_yaml::to_string(&doc).context("Failed to serialize workflow YAML")
+}
+
+/// Save a workflow to a YAML file.
+pub fn save_file(workflow: &Workflow, path: impl AsRef<Path>) -> Result<()> {
     let path = path.as_ref();
-    let content = std::fs::read_to_string(path)
-        .with_context(|| format!("Failed to read workflow file: {}", path.display()))?;
+    let yaml = to_yaml(workflow)?;
 
-    parse_yaml(&content)
-        .with_context(|| format!("Failed to parse workflow file: {}", path.display()))
+    std::fs::write(path, yaml)
+        .with_context(|| format!("Failed to write workflow file: {}", path.display()))
 }
 
-/// Validate a workflow.
-fn validate(workflow: &Workflow) -> Result<()> {
-    if workflow.name.is_empty() {
-        anyhow::bail!("Workflow name cannot be empty");
-    }
+/// Replace `!raw` and `!template` tags with their marker objects.
+///
+/// `!raw value` becomes `{"__raw__": value}` and `!template "..."` becomes
+/// `{"__template__": "..."}`.
+pub(crate) fn expand_tags(value: serde_yaml::Value) -> Result<serde_yaml::Value> {
+    use serde_yaml::{Mapping, Value};
 
-    if workflow.steps.is_empty() {
-        anyhow::bail!("Workflow must have at least one step");
-    }
+    let marker = |key: &str, value: Value| {
+        let mut map = Mapping::new();
+        map.insert(Value::String(key.to_string()), value);
+        Value::Mapping(map)
+    };
 
-    for (i, step) in workflow.steps.iter().enumerate() {
-        if step.service.is_empty() {
-            anyhow::bail!("Step {} has empty service name", i);
+    Ok(match value {
+        Value::Tagged(tagged) => {
+            let inner = expand_tags(tagged.value)?;
+            if tagged.tag == "raw" {
+                marker(RAW_KEY, inner)
+            } else if tagged.tag == "template" {
+                if !inner.is_string() {
+                    anyhow::bail!("!template must be applied to a string");
+                }
+                marker(TEMPLATE_KEY, inner)
+            } else {
+                anyhow::bail!("Unknown YAML tag {}", tagged.tag);
+            }
         }
-        if step.method.is_empty() {
-            anyhow::bail!("Step {} has empty method name", i);
+        Value::Sequence(seq) => Value::Sequence(
+            seq.into_iter()
+                .map(expand_tags)
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        Value::Mapping(map) => {
+            let mut expanded = Mapping::new();
+            for (k, v) in map {
+                expanded.insert(k, expand_tags(v)?);
+            }
+            Value::Mapping(expanded)
         }
-    }
-
-    Ok(())
+        other => other,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::diagnostic::{Diagnostic, Location};
+    use crate::{Output, Param, TemplateMode};
 
     #[test]
     fn test_parse_simple_workflow() {
@@ -120,7 +170,261 @@ steps:
 
         let workflow = parse_yaml(yaml).unwrap();
         assert_eq!(workflow.steps.len(), 2);
-        assert_eq!(workflow.steps[0].output, Some("emails".to_string()));
+        assert_eq!(workflow.steps[0].output, Some(Output::from("emails")));
+    }
+
+    #[test]
+    fn test_parse_transform_step() {
+        let yaml = r#"
+name: merge
+steps:
+  - service: gmail
+    method: gmail.inbox
+    output: inbox
+  - set:
+      count: "{{ inbox.total }}"
+      first: "{{ inbox.messages.0 }}"
+    output: summary
+"#;
+
+        let workflow = parse_yaml(yaml).unwrap();
+        assert!(!workflow.steps[0].is_transform());
+        assert!(workflow.steps[1].is_transform());
+        assert_eq!(workflow.steps[1].output, Some(Output::from("summary")));
+    }
+
+    #[test]
+    fn test_validate_transform_with_service() {
+        let yaml = r#"
+name: bad-transform
+steps:
+  - service: gmail
+    method: gmail.inbox
+    transform: "{{ prev }}"
+"#;
+
+        let result = parse_yaml(yaml);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("cannot call a service"));
+    }
+
+    #[test]
+    fn test_parse_output_bindings() {
+        let yaml = r#"
+name: paged-inbox
+steps:
+  - service: gmail
+    method: gmail.inbox
+    output:
+      emails: messages
+      cursor: next_page_token
+"#;
+
+        let workflow = parse_yaml(yaml).unwrap();
+        let names = workflow.steps[0].output.as_ref().unwrap().names();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"emails") && names.contains(&"cursor"));
+    }
+
+    #[test]
+    fn test_parse_templated_service() {
+        let yaml = r#"
+name: routed
+steps:
+  - service: "gmail-{{ account }}"
+    method: gmail.inbox
+"#;
+
+        let workflow = parse_yaml(yaml).unwrap();
+        assert_eq!(workflow.steps[0].service, "gmail-{{ account }}");
+    }
+
+    #[test]
+    fn test_validate_invalid_method_name() {
+        let yaml = r#"
+name: bad-method
+steps:
+  - service: gmail
+    method: "gmail inbox"
+"#;
+
+        let result = parse_yaml(yaml);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("invalid method name"));
+    }
+
+    #[test]
+    fn test_parse_raw_and_template_tags() {
+        let yaml = r#"
+name: tags
+templates: explicit
+steps:
+  - service: render
+    method: render.mustache
+    params:
+      body: !raw "Hello {{ name }}"
+      greeting: !template "Hi {{ user }}"
+      inline: "{{ untouched }}"
+"#;
+
+        let workflow = parse_yaml(yaml).unwrap();
+        let params = &workflow.steps[0].params;
+        assert_eq!(workflow.templates, TemplateMode::Explicit);
+        assert_eq!(
+            params.get("body"),
+            Some(&Param::literal("Hello {{ name }}"))
+        );
+        assert_eq!(
+            params.get("greeting"),
+            Some(&Param::template("Hi {{ user }}"))
+        );
+        assert_eq!(
+            params.get("inline"),
+            Some(&Param::literal("{{ untouched }}"))
+        );
+    }
+
+    #[test]
+    fn test_auto_mode_inline_template() {
+        let yaml = r#"
+name: auto
+steps:
+  - service: browser
+    method: browser.open
+    params:
+      url: "{{ emails.0.link }}"
+      body: !raw "{{ literal }}"
+"#;
+
+        let workflow = parse_yaml(yaml).unwrap();
+        let params = &workflow.steps[0].params;
+        assert_eq!(
+            params.get("url"),
+            Some(&Param::template("{{ emails.0.link }}"))
+        );
+        assert_eq!(params.get("body"), Some(&Param::literal("{{ literal }}")));
+    }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let workflow = crate::Workflow::new("round-trip")
+            .add(
+                crate::Step::call("browser", "browser.open")
+                    .with_template_param("url", "{{ emails.0.link }}")
+                    .with_raw_param("body", "{{ literal }}")
+                    .with_param("options", serde_json::json!({"tabs": ["{{ tab }}", 1]})),
+            )
+            .build();
+
+        let yaml = serde_yaml::to_string(&workflow).unwrap();
+        let parsed = parse_yaml(&yaml).unwrap();
+
+        assert!(yaml.contains("url: '{{ emails.0.link }}'"));
+        assert_eq!(parsed.steps[0].params, workflow.steps[0].params);
+    }
+
+    #[test]
+    fn test_to_yaml_round_trip() {
+        let workflow = crate::Workflow::new("round-trip")
+            .description("Saved workflow")
+            .add(
+                crate::Step::call("gmail", "gmail.search")
+                    .with_param("query", "is:unread")
+                    .with_param("limit", 5)
+                    .with_param("after", "{{ since }}")
+                    .with_raw_param("snippet", "{{ literal }}")
+                    .with_raw_param("marker", serde_json::json!({"__template__": "x"}))
+                    .output("emails"),
+            )
+            .add(crate::Step::transform("{{ emails.0 }}").id("first"))
+            .build();
+
+        let yaml = to_yaml(&workflow).unwrap();
+        let parsed = parse_yaml(&yaml).unwrap();
+
+        assert_eq!(parsed, workflow);
+        assert_eq!(to_yaml(&parsed).unwrap(), yaml);
+        assert!(yaml.contains("snippet: !raw '{{ literal }}'"));
+
+        let keys: Vec<&String> = parsed.steps[0].params.keys().collect();
+        assert_eq!(keys, ["query", "limit", "after", "snippet", "marker"]);
+    }
+
+    #[test]
+    fn test_to_yaml_explicit_round_trip() {
+        let yaml = r#"
+name: explicit
+templates: explicit
+steps:
+  - service: render
+    method: render.mustache
+    params:
+      body: "Hello {{ name }}"
+      to: !template "{{ user.email }}"
+"#;
+
+        let workflow = parse_yaml(yaml).unwrap();
+        let saved = to_yaml(&workflow).unwrap();
+
+        assert!(saved.contains("to: !template '{{ user.email }}'"));
+        assert_eq!(parse_yaml(&saved).unwrap(), workflow);
+    }
+
+    #[test]
+    fn test_save_and_load_file() {
+        let workflow = crate::Workflow::new("saved")
+            .add(crate::Step::call("gmail", "gmail.inbox").with_param("limit", 5))
+            .build();
+        let dir = crate::testing::TempDir::new("yaml");
+        let path = dir.join("saved.yaml");
+
+        save_file(&workflow, &path).unwrap();
+        let loaded = load_file(&path).unwrap();
+
+        assert_eq!(loaded, workflow);
+    }
+
+    #[test]
+    fn test_unknown_tag() {
+        let yaml = r#"
+name: tags
+steps:
+  - service: test
+    method: test.action
+    params:
+      value: !secret token
+"#;
+
+        let result = parse_yaml(yaml);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Unknown YAML tag"));
+    }
+
+    #[test]
+    fn test_validate_duplicate_step_id() {
+        let yaml = r#"
+name: dup-ids
+steps:
+  - id: inbox
+    service: gmail
+    method: gmail.inbox
+  - id: inbox
+    service: gmail
+    method: gmail.unread
+"#;
+
+        let result = parse_yaml(yaml);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("duplicate id 'inbox'"));
     }
 
     #[test]
@@ -154,4 +458,79 @@ steps: []
             .to_string()
             .contains("at least one step"));
     }
+
+    #[test]
+    fn test_validation_error_location() {
+        let yaml = "name: located\nsteps:\n  - service: gmail\n    method: \"gmail inbox\"\n";
+
+        let err = parse_yaml(yaml).unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+
+        assert_eq!(diagnostic.path, "steps.0.method");
+        assert_eq!(
+            diagnostic.location,
+            Some(Location {
+                line: 4,
+                column: 13
+            })
+        );
+        assert_eq!(
+            diagnostic.snippet.as_deref(),
+            Some("    method: \"gmail inbox\"")
+        );
+        assert!(err
+            .to_string()
+            .starts_with("4:13: error: Step 0 has invalid method name"));
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let yaml = "name: broken\nsteps:\n  - service: gmail\n   method: gmail.inbox\n";
+
+        let err = parse_yaml(yaml).unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+
+        assert_eq!(diagnostic.location.map(|l| l.line), Some(4));
+        assert!(diagnostic
+            .message
+            .starts_with("Failed to parse workflow YAML"));
+    }
+
+    #[test]
+    fn test_invalid_template_location() {
+        let yaml = r#"
+name: bad-template
+steps:
+  - service: browser
+    method: browser.open
+    params:
+      url: "{{#if x}}"
+"#;
+
+        let err = parse_yaml(yaml).unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+
+        assert_eq!(diagnostic.path, "steps.0.params.url");
+        assert!(diagnostic.message.contains("invalid template"));
+        assert_eq!(diagnostic.location.map(|l| l.line), Some(7));
+    }
+
+    #[test]
+    fn test_invalid_step_location() {
+        let yaml = r#"
+name: bad-step
+steps:
+  - service: gmail
+    method: gmail.inbox
+  - service: gmail
+    method: gmail.inbox
+    params: [1, 2]
+"#;
+
+        let err = parse_yaml(yaml).unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+
+        assert_eq!(diagnostic.path, "steps.1");
+        assert_eq!(diagnostic.location.map(|l| l.line), Some(6));
+    }
 }